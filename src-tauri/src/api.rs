@@ -0,0 +1,396 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::Settings;
+
+// Attempts per request, including the first one
+const MAX_ATTEMPTS: u32 = 4;
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(8);
+// Give up instead of waiting when the server asks for a longer pause than this
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+// Failure from a transcription or formatting API. Serialized with a `kind` tag so
+// the frontend can suggest a fix instead of just showing the server's message.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ApiError {
+    // API key missing or rejected
+    Auth { message: String },
+    // Account is out of credits or over its usage limit
+    QuotaExceeded { message: String },
+    // Still rate limited after retrying
+    RateLimited { message: String, retry_after_secs: Option<u64> },
+    // Server kept failing after retrying
+    Server { status: u16, message: String },
+    // Any other rejected request, e.g. an unknown model
+    Request { status: u16, message: String },
+    // Server couldn't be reached
+    Network { message: String },
+    // Response didn't have the expected shape
+    Parse { message: String },
+    // Nothing was recorded, or the audio couldn't be encoded or decoded
+    Audio { message: String },
+    // A cached recording to re-run was evicted or can't be read
+    RecordingUnavailable { message: String },
+    // Local Whisper model missing, not built in, or failed to run
+    LocalModel { message: String },
+    // Another transcription is still running
+    Busy { message: String },
+    // Anything else, e.g. app state that couldn't be read
+    Other { message: String },
+}
+
+impl ApiError {
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::Auth { message }
+            | ApiError::QuotaExceeded { message }
+            | ApiError::RateLimited { message, .. }
+            | ApiError::Server { message, .. }
+            | ApiError::Request { message, .. }
+            | ApiError::Network { message }
+            | ApiError::Parse { message }
+            | ApiError::Audio { message }
+            | ApiError::RecordingUnavailable { message }
+            | ApiError::LocalModel { message }
+            | ApiError::Busy { message }
+            | ApiError::Other { message } => message,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        matches!(self, ApiError::RateLimited { .. } | ApiError::Server { .. })
+    }
+
+    // Classify a non-success response. `service` names the API in messages, e.g. "Whisper".
+    async fn from_response(service: &str, response: reqwest::Response) -> ApiError {
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        let (detail, code) = error_detail(&body);
+        let detail = detail.unwrap_or_else(|| status.canonical_reason().unwrap_or("no details").to_string());
+        let message = format!("{} API error ({}): {}", service, status.as_u16(), detail);
+
+        match status.as_u16() {
+            401 | 403 => ApiError::Auth { message },
+            402 => ApiError::QuotaExceeded { message },
+            // OpenAI reports an exhausted balance as a 429 that retrying won't fix
+            429 if code.as_deref() == Some("insufficient_quota") => ApiError::QuotaExceeded { message },
+            429 => ApiError::RateLimited {
+                message,
+                retry_after_secs: retry_after.map(|d| d.as_secs()),
+            },
+            500..=599 => ApiError::Server {
+                status: status.as_u16(),
+                message,
+            },
+            _ => ApiError::Request {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError::Other { message }
+    }
+}
+
+impl From<&str> for ApiError {
+    fn from(message: &str) -> Self {
+        ApiError::Other {
+            message: message.to_string(),
+        }
+    }
+}
+
+// HTTP client shared by every API call so connections are reused. Rebuilt when the
+// network settings change; clones are cheap and share the same pool.
+pub struct HttpClient {
+    client: Mutex<reqwest::Client>,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            client: Mutex::new(build_client(&Settings::default()).unwrap_or_default()),
+        }
+    }
+}
+
+impl HttpClient {
+    pub fn get(&self) -> Result<reqwest::Client, String> {
+        let client = self.client.lock().map_err(|e| e.to_string())?;
+        Ok(client.clone())
+    }
+
+    // Apply timeouts and proxy from settings. Requests already in flight keep the old client.
+    pub fn configure(&self, settings: &Settings) -> Result<(), String> {
+        let new_client = build_client(settings)?;
+        let mut client = self.client.lock().map_err(|e| e.to_string())?;
+        *client = new_client;
+        Ok(())
+    }
+}
+
+pub fn build_client(settings: &Settings) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+        .timeout(Duration::from_secs(settings.request_timeout_secs));
+
+    if let Some(proxy_url) = &settings.proxy_url {
+        // Local servers such as Ollama are never reached through the proxy
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL: {}", e))?
+            .no_proxy(reqwest::NoProxy::from_string("localhost,127.0.0.1,::1"));
+        builder = builder.proxy(proxy);
+    }
+
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+// Send a request, retrying rate limits and server errors with exponential backoff.
+// `build` is called once per attempt since request bodies (multipart forms in
+// particular) can't be reused.
+pub async fn send_with_retry<F>(service: &str, build: F) -> Result<reqwest::Response, ApiError>
+where
+    F: Fn() -> Result<reqwest::RequestBuilder, ApiError>,
+{
+    let mut attempt = 1;
+    loop {
+        let response = build()?.send().await.map_err(|e| ApiError::Network {
+            message: if e.is_timeout() {
+                format!("{} request timed out", service)
+            } else {
+                format!("{} request failed: {}", service, e)
+            },
+        })?;
+
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let retry_after = retry_after(response.headers());
+        let error = ApiError::from_response(service, response).await;
+        if !error.is_retryable() || attempt >= MAX_ATTEMPTS {
+            return Err(error);
+        }
+
+        let delay = retry_after.unwrap_or_else(|| backoff_delay(attempt));
+        if delay > MAX_RETRY_DELAY {
+            return Err(error);
+        }
+
+        println!("{} (attempt {}/{}), retrying in {} ms", error, attempt, MAX_ATTEMPTS, delay.as_millis());
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// Decode a successful JSON response
+pub async fn parse_json<T: DeserializeOwned>(service: &str, response: reqwest::Response) -> Result<T, ApiError> {
+    response.json().await.map_err(|e| ApiError::Parse {
+        message: format!("Unexpected {} API response: {}", service, e),
+    })
+}
+
+// Doubling delay with random jitter, so clients that failed together don't retry together
+fn backoff_delay(attempt: u32) -> Duration {
+    use std::hash::BuildHasher;
+
+    let delay = BASE_RETRY_DELAY
+        .saturating_mul(1u32 << (attempt - 1).min(16))
+        .min(MAX_BACKOFF_DELAY);
+    let half = delay.as_millis() as u64 / 2;
+    // RandomState is freshly seeded on every call, which is plenty for jitter
+    let jitter = std::collections::hash_map::RandomState::new().hash_one(attempt) % (half + 1);
+    Duration::from_millis(half + jitter)
+}
+
+// Retry-After in seconds, or OpenAI's millisecond variant. HTTP-date values are
+// ignored and fall back to our own backoff. Values too large for a Duration become
+// Duration::MAX, which is always more than we're willing to wait.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| {
+        let value = headers.get(name)?.to_str().ok()?.trim().parse::<f64>().ok()?;
+        value.is_finite().then_some(value.max(0.0))
+    };
+    let duration = |secs: f64| Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX);
+
+    header("retry-after-ms")
+        .map(|ms| duration(ms / 1000.0))
+        .or_else(|| header("retry-after").map(duration))
+}
+
+// Pull the human-readable message and error code out of an error body. Handles
+// OpenAI's {"error": {"message", "code"}} and Ollama's {"error": "..."}.
+fn error_detail(body: &str) -> (Option<String>, Option<String>) {
+    let body = body.trim();
+    let json: serde_json::Value = match serde_json::from_str(body) {
+        Ok(json) => json,
+        Err(_) => return ((!body.is_empty()).then(|| body.to_string()), None),
+    };
+
+    let error = &json["error"];
+    let message = error["message"]
+        .as_str()
+        .or_else(|| error.as_str())
+        .or_else(|| json["message"].as_str())
+        .map(|s| s.to_string())
+        .or_else(|| Some(body.to_string()));
+    let code = error["code"]
+        .as_str()
+        .or_else(|| error["type"].as_str())
+        .map(|s| s.to_string());
+
+    (message, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn retry_after_parses_seconds_and_milliseconds() {
+        assert_eq!(retry_after(&headers(&[("retry-after", "2")])), Some(Duration::from_secs(2)));
+        assert_eq!(retry_after(&headers(&[("retry-after", "0.5")])), Some(Duration::from_millis(500)));
+        assert_eq!(retry_after(&headers(&[("retry-after-ms", "1500")])), Some(Duration::from_millis(1500)));
+        // The millisecond header is more precise, so it wins
+        let both = headers(&[("retry-after", "9"), ("retry-after-ms", "20")]);
+        assert_eq!(retry_after(&both), Some(Duration::from_millis(20)));
+        assert_eq!(retry_after(&headers(&[("retry-after", "-3")])), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_ignores_dates_and_survives_huge_values() {
+        assert_eq!(retry_after(&headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")])), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+        assert_eq!(retry_after(&headers(&[("retry-after", "99999999999999999999")])), Some(Duration::MAX));
+        assert_eq!(retry_after(&headers(&[("retry-after-ms", "1e300")])), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_and_is_capped() {
+        for _ in 0..20 {
+            let first = backoff_delay(1);
+            assert!(first >= BASE_RETRY_DELAY / 2 && first <= BASE_RETRY_DELAY, "{:?}", first);
+            let third = backoff_delay(3);
+            assert!(third >= BASE_RETRY_DELAY * 2 && third <= BASE_RETRY_DELAY * 4, "{:?}", third);
+            let late = backoff_delay(40);
+            assert!(late >= MAX_BACKOFF_DELAY / 2 && late <= MAX_BACKOFF_DELAY, "{:?}", late);
+        }
+    }
+
+    #[test]
+    fn error_detail_reads_openai_and_ollama_bodies() {
+        let openai = r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}}"#;
+        assert_eq!(
+            error_detail(openai),
+            (Some("You exceeded your current quota".to_string()), Some("insufficient_quota".to_string()))
+        );
+        let typed = r#"{"error": {"message": "Bad", "type": "invalid_request_error"}}"#;
+        assert_eq!(error_detail(typed).1.as_deref(), Some("invalid_request_error"));
+        assert_eq!(error_detail(r#"{"error": "model not found"}"#), (Some("model not found".to_string()), None));
+        assert_eq!(error_detail(r#"{"message": "Unauthorized"}"#).0.as_deref(), Some("Unauthorized"));
+        assert_eq!(error_detail("  Bad Gateway\n").0.as_deref(), Some("Bad Gateway"));
+        assert_eq!(error_detail(""), (None, None));
+    }
+
+    async fn send(server: &MockServer) -> Result<reqwest::Response, ApiError> {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let url = format!("{}/test", server.url);
+        send_with_retry("Test", || Ok(client.get(&url))).await
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limits_honoring_retry_after() {
+        let server = MockServer::start(vec![
+            MockResponse::json(429, r#"{"error": {"message": "Slow down"}}"#).header("retry-after-ms", "10"),
+            MockResponse::json(200, "{}"),
+        ])
+        .await;
+
+        let started = std::time::Instant::now();
+        let response = send(&server).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.requests().len(), 2);
+        // The server's 10 ms, not our 250 ms minimum backoff
+        assert!(started.elapsed() < BASE_RETRY_DELAY / 2, "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_with_backoff() {
+        let server = MockServer::start(vec![MockResponse::json(503, ""), MockResponse::json(200, "{}")]).await;
+
+        let started = std::time::Instant::now();
+        send(&server).await.unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+        assert!(started.elapsed() >= BASE_RETRY_DELAY / 2, "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let server = MockServer::start(vec![MockResponse::json(500, "oops").header("retry-after-ms", "1")]).await;
+
+        let error = send(&server).await.unwrap_err();
+
+        assert!(matches!(error, ApiError::Server { status: 500, .. }), "{:?}", error);
+        assert_eq!(server.requests().len(), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn never_retries_auth_or_quota_errors() {
+        for (status, body) in [
+            (401, r#"{"error": {"message": "Incorrect API key"}}"#),
+            (402, r#"{"error": {"message": "Payment required"}}"#),
+            (429, r#"{"error": {"message": "Quota", "code": "insufficient_quota"}}"#),
+        ] {
+            let server = MockServer::start(vec![MockResponse::json(status, body), MockResponse::json(200, "{}")]).await;
+
+            let error = send(&server).await.unwrap_err();
+
+            assert!(matches!(error, ApiError::Auth { .. } | ApiError::QuotaExceeded { .. }), "{:?}", error);
+            assert_eq!(server.requests().len(), 1, "status {}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_asked_to_wait_too_long() {
+        for retry_after in ["60", "99999999999999999999"] {
+            let server = MockServer::start(vec![
+                MockResponse::json(429, "").header("retry-after", retry_after),
+                MockResponse::json(200, "{}"),
+            ])
+            .await;
+
+            let error = send(&server).await.unwrap_err();
+
+            assert!(matches!(error, ApiError::RateLimited { .. }), "{:?}", error);
+            assert_eq!(server.requests().len(), 1);
+        }
+    }
+}
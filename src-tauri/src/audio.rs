@@ -0,0 +1,333 @@
+// Audio processing helpers applied to captured input before it's encoded
+
+use std::sync::OnceLock;
+
+// Convert interleaved multi-channel samples to mono.
+// If `channel` names a valid channel only that one is kept, otherwise all channels are averaged.
+pub fn downmix_to_mono(samples: &[f32], channels: usize, channel: Option<usize>) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+
+    match channel {
+        Some(ch) if ch < channels => samples
+            .chunks_exact(channels)
+            .map(|frame| frame[ch])
+            .collect(),
+        _ => samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect(),
+    }
+}
+
+// Whisper works on 16 kHz mono internally, so anything above that is wasted upload
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+// Number of sinc lobes on each side of the interpolation point
+const SINC_ZERO_CROSSINGS: usize = 16;
+// Kernel table entries per lobe; weights in between are linearly interpolated
+const KERNEL_RESOLUTION: usize = 256;
+
+// Resample mono audio with a Hann-windowed sinc kernel.
+// When downsampling the kernel is stretched so it also low-passes below the new Nyquist.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let kernel = kernel();
+    let ratio = to_rate as f64 / from_rate as f64;
+    let cutoff = ratio.min(1.0);
+    let half_width = SINC_ZERO_CROSSINGS as f64 / cutoff;
+    let out_len = (samples.len() as f64 * ratio).round() as usize;
+
+    let mut output = Vec::with_capacity(out_len);
+    for n in 0..out_len {
+        // Position of this output sample on the input timeline
+        let t = n as f64 / ratio;
+        let first = ((t - half_width).ceil() as isize).max(0);
+        let last = ((t + half_width).floor() as isize).min(samples.len() as isize - 1);
+
+        let mut acc = 0.0;
+        let mut weight_sum = 0.0;
+        for i in first..=last {
+            let weight = kernel_weight(kernel, (t - i as f64).abs() * cutoff);
+            acc += samples[i as usize] as f64 * weight;
+            weight_sum += weight;
+        }
+
+        // Normalizing keeps unity gain, including near the edges where the kernel is cut off
+        output.push(if weight_sum.abs() > f64::EPSILON {
+            (acc / weight_sum) as f32
+        } else {
+            0.0
+        });
+    }
+
+    output
+}
+
+// The kernel table only depends on constants, so it is built once and shared
+fn kernel() -> &'static [f64] {
+    static KERNEL: OnceLock<Vec<f64>> = OnceLock::new();
+    KERNEL.get_or_init(build_kernel)
+}
+
+// Tabulate sinc(x) * hann(x) for x in [0, SINC_ZERO_CROSSINGS]
+fn build_kernel() -> Vec<f64> {
+    let len = SINC_ZERO_CROSSINGS * KERNEL_RESOLUTION + 1;
+    (0..len)
+        .map(|i| {
+            let x = i as f64 / KERNEL_RESOLUTION as f64;
+            let window = 0.5 * (1.0 + (std::f64::consts::PI * x / SINC_ZERO_CROSSINGS as f64).cos());
+            sinc(x) * window
+        })
+        .collect()
+}
+
+fn kernel_weight(kernel: &[f64], x: f64) -> f64 {
+    let pos = x * KERNEL_RESOLUTION as f64;
+    let index = pos as usize;
+    if index + 1 >= kernel.len() {
+        return 0.0;
+    }
+    let frac = pos - index as f64;
+    kernel[index] + (kernel[index + 1] - kernel[index]) * frac
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+// Frame length used for speech detection
+const VAD_FRAME_MS: usize = 30;
+// Audio kept on either side of detected speech so onsets and tails aren't clipped
+const VAD_PADDING_MS: usize = 250;
+// Frames quieter than this (about -50 dBFS) never count as speech
+const VAD_MIN_RMS: f32 = 0.003;
+// Speech frames must be this many times louder than the estimated noise floor
+const VAD_NOISE_MULTIPLIER: f32 = 3.0;
+// Highest believable noise floor (about -40 dBFS). A clip with speech from start to
+// end has no quiet frames, and its own speech level mustn't count as the noise.
+const VAD_MAX_NOISE_RMS: f32 = 0.01;
+// Less voiced audio than this is treated as a click or bump rather than speech
+const VAD_MIN_SPEECH_MS: usize = 150;
+
+// Find the span of a mono recording that contains speech, padded on both sides.
+// Returns None when nothing in the recording rises above the noise floor.
+pub fn detect_speech(samples: &[f32], sample_rate: u32) -> Option<std::ops::Range<usize>> {
+    let frame_len = (sample_rate as usize * VAD_FRAME_MS / 1000).max(1);
+    let energies: Vec<f32> = samples.chunks(frame_len).map(rms).collect();
+    if energies.is_empty() {
+        return None;
+    }
+
+    // Estimate background noise from the quietest frames
+    let mut sorted = energies.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let noise_floor = sorted[sorted.len() / 10].min(VAD_MAX_NOISE_RMS);
+    let threshold = (noise_floor * VAD_NOISE_MULTIPLIER).max(VAD_MIN_RMS);
+
+    let voiced: Vec<bool> = energies.iter().map(|&e| e > threshold).collect();
+    let voiced_frames = voiced.iter().filter(|&&v| v).count();
+    if voiced_frames * VAD_FRAME_MS < VAD_MIN_SPEECH_MS {
+        return None;
+    }
+
+    let first = voiced.iter().position(|&v| v)?;
+    let last = voiced.iter().rposition(|&v| v)?;
+    let padding = sample_rate as usize * VAD_PADDING_MS / 1000;
+
+    let start = (first * frame_len).saturating_sub(padding);
+    let end = ((last + 1) * frame_len + padding).min(samples.len());
+    Some(start..end)
+}
+
+fn rms(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Left channel 0.2, 0.4, 0.6; right channel 0.6, 0.8, 1.0
+    const STEREO: [f32; 6] = [0.2, 0.6, 0.4, 0.8, 0.6, 1.0];
+
+    fn sine(frequency: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let len = (sample_rate as f32 * seconds) as usize;
+        (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    // Deterministic white noise at the given peak level
+    fn noise(level: f32, len: usize) -> Vec<f32> {
+        let mut state: u32 = 12345;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 * 2.0 * level - level
+            })
+            .collect()
+    }
+
+    // A voiced tone whose loudness rises and falls like syllables, 4 times a second
+    fn syllables(amplitude: f32, depth: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        sine(150.0, sample_rate, seconds)
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let t = i as f32 / sample_rate as f32;
+                let envelope = 1.0 - depth * (0.5 + 0.5 * (2.0 * std::f32::consts::PI * 4.0 * t).cos());
+                s * amplitude * envelope
+            })
+            .collect()
+    }
+
+    // Crossings per second, skipping the edges where the kernel is cut off
+    fn zero_crossing_rate(samples: &[f32], sample_rate: u32) -> f32 {
+        let edge = samples.len() / 10;
+        let middle = &samples[edge..samples.len() - edge];
+        let crossings = middle.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+        crossings as f32 * sample_rate as f32 / middle.len() as f32
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn downmix_averages_channels() {
+        assert_close(&downmix_to_mono(&STEREO, 2, None), &[0.4, 0.6, 0.8]);
+    }
+
+    #[test]
+    fn downmix_keeps_selected_channel() {
+        assert_close(&downmix_to_mono(&STEREO, 2, Some(0)), &[0.2, 0.4, 0.6]);
+        assert_close(&downmix_to_mono(&STEREO, 2, Some(1)), &[0.6, 0.8, 1.0]);
+    }
+
+    #[test]
+    fn downmix_averages_when_channel_out_of_range() {
+        assert_close(&downmix_to_mono(&STEREO, 2, Some(2)), &[0.4, 0.6, 0.8]);
+    }
+
+    #[test]
+    fn downmix_drops_trailing_partial_frame() {
+        assert_close(&downmix_to_mono(&[0.2, 0.6, 0.4, 0.8, 0.6], 2, None), &[0.4, 0.6]);
+        assert_close(&downmix_to_mono(&[0.2, 0.6, 0.4], 2, Some(1)), &[0.6]);
+    }
+
+    #[test]
+    fn downmix_passes_mono_through() {
+        assert_close(&downmix_to_mono(&[0.1, 0.2], 1, Some(3)), &[0.1, 0.2]);
+    }
+
+    #[test]
+    fn resample_preserves_duration_and_frequency() {
+        let input = sine(1000.0, 48000, 1.0);
+        let output = resample(&input, 48000, WHISPER_SAMPLE_RATE);
+
+        assert_eq!(output.len(), 16000);
+        // A 1 kHz sine crosses zero 2000 times a second
+        let rate = zero_crossing_rate(&output, WHISPER_SAMPLE_RATE);
+        assert!((rate - 2000.0).abs() < 20.0, "zero-crossing rate {}", rate);
+    }
+
+    #[test]
+    fn resample_upsampling_preserves_frequency() {
+        let input = sine(440.0, 16000, 0.5);
+        let output = resample(&input, 16000, 44100);
+
+        assert_eq!(output.len(), 22050);
+        let rate = zero_crossing_rate(&output, 44100);
+        assert!((rate - 880.0).abs() < 10.0, "zero-crossing rate {}", rate);
+    }
+
+    #[test]
+    fn resample_filters_above_new_nyquist() {
+        // 12 kHz can't be represented at 16 kHz and should be mostly removed
+        let output = resample(&sine(12000.0, 48000, 0.5), 48000, WHISPER_SAMPLE_RATE);
+        let edge = output.len() / 10;
+        assert!(rms(&output[edge..output.len() - edge]) < 0.05);
+    }
+
+    #[test]
+    fn resample_same_rate_is_identity() {
+        let input = sine(1000.0, 16000, 0.1);
+        assert_eq!(resample(&input, 16000, 16000), input);
+    }
+
+    #[test]
+    fn silence_has_no_speech() {
+        assert_eq!(detect_speech(&[], 16000), None);
+        assert_eq!(detect_speech(&vec![0.0; 16000], 16000), None);
+        assert_eq!(detect_speech(&noise(0.002, 16000), 16000), None);
+    }
+
+    #[test]
+    fn short_click_is_not_speech() {
+        let mut samples = noise(0.002, 16000);
+        samples[8000..8800].iter_mut().for_each(|s| *s = 0.5);
+        assert_eq!(detect_speech(&samples, 16000), None);
+    }
+
+    #[test]
+    fn speech_only_clip_is_kept_whole() {
+        // Push-to-talk held only while speaking: no quiet frames to measure noise from
+        let tone: Vec<f32> = sine(200.0, 16000, 1.5).into_iter().map(|s| s * 0.2).collect();
+        assert_eq!(detect_speech(&tone, 16000), Some(0..tone.len()));
+
+        for depth in [0.3, 0.5, 0.7] {
+            let speech = syllables(0.2, depth, 16000, 1.5);
+            assert_eq!(detect_speech(&speech, 16000), Some(0..speech.len()), "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn speech_is_trimmed_with_padding() {
+        let sample_rate = 16000;
+        let second = sample_rate as usize;
+        let mut samples = noise(0.002, second);
+        samples.extend(syllables(0.2, 0.5, sample_rate, 0.5));
+        samples.extend(noise(0.002, 2 * second));
+
+        let range = detect_speech(&samples, sample_rate).unwrap();
+        let padding = second * VAD_PADDING_MS / 1000;
+        let frame = second * VAD_FRAME_MS / 1000;
+        let (speech_start, speech_end) = (second, second + second / 2);
+        assert!(range.start <= speech_start - padding && range.start + frame > speech_start - padding, "{:?}", range);
+        assert!(range.end >= speech_end + padding && range.end < speech_end + padding + frame, "{:?}", range);
+    }
+
+    #[test]
+    fn padding_is_clamped_to_the_recording() {
+        let mut samples = syllables(0.2, 0.5, 16000, 0.5);
+        samples.extend(noise(0.002, 1000));
+        assert_eq!(detect_speech(&samples, 16000), Some(0..samples.len()));
+    }
+
+    #[test]
+    fn speech_stands_out_from_steady_noise() {
+        let mut samples = noise(0.02, 32000);
+        let speech = syllables(0.3, 0.3, 16000, 0.5);
+        for (s, v) in samples[8000..].iter_mut().zip(&speech) {
+            *s += v;
+        }
+        let range = detect_speech(&samples, 16000).unwrap();
+        assert!(range.start > 0 && range.end < samples.len(), "{:?}", range);
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::injection::InjectionResult;
+use crate::{AppState, AudioState, Settings, TranscriptionOutcome, TranscriptionState};
+
+// How the record hotkey behaves
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyMode {
+    // Record while the keys are held down (push-to-talk)
+    #[default]
+    Hold,
+    // First press starts recording, second press stops it
+    Toggle,
+}
+
+// Why a hotkey couldn't be used, tagged by `kind` for the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HotkeyError {
+    // Not a combination the shortcut parser understands
+    Invalid { hotkey: String, message: String },
+    // Registration failed, usually because another application owns the combination
+    Unavailable { hotkey: String, message: String },
+    // Record and cancel hotkeys are the same combination
+    Conflict { hotkey: String, message: String },
+    Other { message: String },
+}
+
+impl fmt::Display for HotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotkeyError::Invalid { message, .. }
+            | HotkeyError::Unavailable { message, .. }
+            | HotkeyError::Conflict { message, .. }
+            | HotkeyError::Other { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for HotkeyError {
+    fn from(message: String) -> Self {
+        HotkeyError::Other { message }
+    }
+}
+
+fn parse_shortcut(hotkey: &str) -> Result<Shortcut, HotkeyError> {
+    hotkey.parse().map_err(|e| HotkeyError::Invalid {
+        hotkey: hotkey.to_string(),
+        message: format!("\"{}\" is not a valid hotkey: {}", hotkey, e),
+    })
+}
+
+// Switch from the hotkeys in `current` to those in `updated`. Registering is the only
+// reliable way to find out whether a combination is free, so the new hotkeys are
+// registered for real; if that fails the previous ones are put back.
+pub fn update_shortcuts(app: &AppHandle, current: &Settings, updated: &Settings) -> Result<(), HotkeyError> {
+    let record = parse_shortcut(&updated.hotkey)?;
+    if let Some(cancel_hotkey) = &updated.cancel_hotkey {
+        if parse_shortcut(cancel_hotkey)? == record {
+            return Err(HotkeyError::Conflict {
+                hotkey: cancel_hotkey.clone(),
+                message: format!("{} is already the record hotkey", cancel_hotkey),
+            });
+        }
+    }
+
+    if let Err(e) = register_shortcuts(app, updated) {
+        if let Err(restore_error) = register_shortcuts(app, current) {
+            eprintln!("Failed to restore previous hotkeys: {}", restore_error);
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Register the record and cancel hotkeys from settings, replacing any registered before.
+// Runs in Rust so the hotkeys work before (or without) the main window loading.
+pub fn register_shortcuts(app: &AppHandle, settings: &Settings) -> Result<(), HotkeyError> {
+    let shortcuts = app.global_shortcut();
+    shortcuts.unregister_all().map_err(|e| e.to_string())?;
+
+    let record = parse_shortcut(&settings.hotkey)?;
+    let mode = settings.hotkey_mode;
+    shortcuts
+        .on_shortcut(record, move |app, _, event| match (mode, event.state()) {
+            (HotkeyMode::Hold, ShortcutState::Pressed) => start(app),
+            (HotkeyMode::Hold, ShortcutState::Released) => stop(app),
+            (HotkeyMode::Toggle, ShortcutState::Pressed) => toggle(app),
+            (HotkeyMode::Toggle, ShortcutState::Released) => {}
+        })
+        .map_err(|e| HotkeyError::Unavailable {
+            hotkey: settings.hotkey.clone(),
+            message: format!("Couldn't register {}, it may be in use by another app: {}", settings.hotkey, e),
+        })?;
+
+    if let Some(cancel_hotkey) = &settings.cancel_hotkey {
+        let cancel_shortcut = parse_shortcut(cancel_hotkey)?;
+        shortcuts
+            .on_shortcut(cancel_shortcut, |app, _, event| {
+                if event.state() == ShortcutState::Pressed {
+                    cancel(app);
+                }
+            })
+            .map_err(|e| HotkeyError::Unavailable {
+                hotkey: cancel_hotkey.clone(),
+                message: format!("Couldn't register {}, it may be in use by another app: {}", cancel_hotkey, e),
+            })?;
+    }
+
+    println!("Registered hotkey {} ({:?} mode)", settings.hotkey, mode);
+    Ok(())
+}
+
+fn is_recording(app: &AppHandle) -> bool {
+    app.state::<AudioState>().is_recording.lock().map(|r| *r).unwrap_or(false)
+}
+
+// Start recording and show the overlay. Key repeat while holding the hotkey is ignored.
+pub fn start(app: &AppHandle) {
+    if is_recording(app) {
+        return;
+    }
+
+    if let Err(e) = crate::start_recording(app.clone(), app.state(), app.state()) {
+        eprintln!("Failed to start recording: {}", e);
+        let _ = app.emit("recording-error", e);
+        return;
+    }
+    let _ = crate::show_overlay(app.clone());
+    let _ = app.emit("recording-started", ());
+}
+
+// Stop recording, then transcribe and paste the result in the background. The
+// outcome of auto-paste is reported separately as `injection-result`.
+pub fn stop(app: &AppHandle) {
+    if !end_recording(app) {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let _ = app.emit("transcription-started", ());
+        let result = crate::transcribe_audio(
+            app.state(),
+            app.state(),
+            app.state(),
+            app.state(),
+            app.state(),
+            app.state(),
+        )
+        .await;
+
+        match result {
+            Ok(result) => {
+                let auto_paste = app.state::<AppState>().settings.lock().map(|s| s.auto_paste).unwrap_or(false);
+                // Cancelled and empty results must never reach the focused app
+                let text = (auto_paste && result.outcome == TranscriptionOutcome::Transcribed)
+                    .then(|| result.formatted_text.clone());
+                let _ = app.emit("transcription-result", result);
+
+                if let Some(text) = text {
+                    let injection = crate::inject_text(text, app.state()).await;
+                    if let Err(e) = &injection {
+                        eprintln!("Failed to paste: {}", e);
+                    }
+                    let _ = app.emit("injection-result", InjectionResult::from(injection));
+                }
+            }
+            Err(e) => {
+                eprintln!("Transcription failed: {}", e);
+                let _ = app.emit("transcription-error", e);
+            }
+        }
+    });
+}
+
+pub fn toggle(app: &AppHandle) {
+    if is_recording(app) {
+        stop(app);
+    } else {
+        start(app);
+    }
+}
+
+// Abandon the current recording without transcribing it, or cancel the transcription
+// in progress
+pub fn cancel(app: &AppHandle) {
+    if end_recording(app) {
+        println!("Recording discarded");
+        let _ = app.emit("dictation-cancelled", ());
+        return;
+    }
+
+    let transcription_state: State<TranscriptionState> = app.state();
+    match transcription_state.cancel() {
+        Ok(true) => {
+            let _ = app.emit("dictation-cancelled", ());
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("Failed to cancel transcription: {}", e),
+    }
+}
+
+// Stop recording and hide the overlay. Returns false if nothing was being recorded.
+fn end_recording(app: &AppHandle) -> bool {
+    if !is_recording(app) {
+        return false;
+    }
+
+    let _ = crate::stop_recording(app.state());
+    let _ = crate::hide_overlay(app.clone());
+    let _ = app.emit("recording-stopped", ());
+    true
+}
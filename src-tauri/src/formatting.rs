@@ -0,0 +1,638 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::api::{self, ApiError};
+use crate::keywords::{KeywordRule, MatchMode};
+use crate::{EndpointConfig, Settings};
+
+// Which engine cleans up raw transcripts
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormattingBackend {
+    // OpenAI chat completions, or any compatible server (LiteLLM, llama.cpp's llama-server, ...)
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    // A local Ollama server via its native chat API
+    Ollama,
+}
+
+// An LLM that turns keyword-processed transcripts into clean text
+#[async_trait]
+pub trait FormattingProvider: Send + Sync {
+    // Identifier for logs and results, e.g. "ollama:llama3.2"
+    fn name(&self) -> String;
+
+    async fn format(&self, system_prompt: &str, text: &str) -> Result<String, ApiError>;
+}
+
+// Build the formatter selected in settings. Returns None when it can't be used,
+// i.e. api.openai.com without an API key.
+pub fn create_provider(settings: &Settings, client: reqwest::Client) -> Option<Box<dyn FormattingProvider>> {
+    match settings.formatting_backend {
+        FormattingBackend::OpenAi => {
+            let endpoint = settings.formatting_endpoint.clone();
+            let api_key = endpoint.resolve_api_key(settings.openai_api_key.as_deref());
+            if api_key.is_none() && endpoint.requires_api_key() {
+                return None;
+            }
+            Some(Box::new(OpenAiFormatter {
+                client,
+                endpoint,
+                api_key,
+                temperature: settings.formatting_temperature,
+                max_tokens: settings.formatting_max_tokens,
+            }))
+        }
+        FormattingBackend::Ollama => Some(Box::new(OllamaFormatter {
+            client,
+            endpoint: settings.ollama_endpoint.clone(),
+            temperature: settings.formatting_temperature,
+            max_tokens: settings.formatting_max_tokens,
+        })),
+    }
+}
+
+// Combine the custom prompt with instructions for the user's keyword replacements.
+// Regex rules are left out: they have already been applied and mean little to the model.
+pub fn build_system_prompt(custom_prompt: &str, keywords: &[KeywordRule]) -> String {
+    let keyword_list: Vec<String> = keywords
+        .iter()
+        .filter(|rule| rule.enabled)
+        .filter_map(|rule| match rule.match_mode {
+            MatchMode::WholeWord => Some(format!("\"{}\" -> \"{}\"", rule.spoken, rule.replacement)),
+            MatchMode::Substring => Some(format!("\"{}\" -> \"{}\" (also inside longer words)", rule.spoken, rule.replacement)),
+            MatchMode::Regex => None,
+        })
+        .collect();
+    if keyword_list.is_empty() {
+        return custom_prompt.to_string();
+    }
+
+    format!(
+        "{}\n\nIMPORTANT: Apply these exact keyword replacements (case-insensitive):\n{}",
+        custom_prompt,
+        keyword_list.join("\n")
+    )
+}
+
+// Openers models use when they reply to the dictation instead of formatting it
+const PREAMBLE_PATTERNS: &[&str] = &[
+    "sure thing",
+    "here's the formatted",
+    "here is the formatted",
+    "here's the cleaned",
+    "here is the cleaned",
+    "certainly",
+    "of course",
+    "absolutely",
+    "i'd be happy",
+    "i would be happy",
+    "i'm sorry",
+    "i am sorry",
+    "as an ai",
+    "great question",
+];
+
+// Assistant phrases that give a reply away wherever they appear
+const ASSISTANT_PHRASES: &[&str] = &[
+    "how can i help",
+    "how can i assist",
+    "i hope this helps",
+    "let me know if you",
+    "feel free to ask",
+];
+
+// Openers of dictated questions. Also common in headings ("How to configure the
+// proxy"), so they only count with a '?' in the input or a reply-style output.
+const QUESTION_OPENERS: &[&str] = &[
+    "what is", "what's", "what are", "how do", "how does", "how can", "how to", "why is",
+    "why does", "who is", "when is", "where is", "can you", "could you", "would you",
+    "will you", "do you", "is there", "are there",
+];
+
+// Fillers skipped when looking for a question opener ("um so what is...")
+const LEADING_FILLERS: &[&str] = &["um", "uh", "so", "ok", "okay", "hey", "and", "well"];
+
+// Acknowledgements skipped before a preamble ("Sure, here's the formatted...")
+const REPLY_FILLERS: &[&str] = &["sure", "ok", "okay", "alright"];
+
+// How answers to a dictated question tend to start
+const ANSWER_OPENERS: &[&str] = &[
+    "yes", "no", "you can", "you could", "you should", "you need", "you'll need", "it is",
+    "it's", "there is", "there are", "the answer", "to do this", "in short",
+];
+
+// Length and overlap checks are too noisy on very short utterances
+const GUARDRAIL_MIN_WORDS: usize = 5;
+// Formatted output may shrink (fillers removed) or grow a little (list markers)
+const MIN_LENGTH_RATIO: f32 = 0.4;
+const MAX_LENGTH_RATIO: f32 = 2.0;
+// Share of output words that must also appear in the input
+const MIN_TOKEN_OVERLAP: f32 = 0.5;
+
+// Check that the formatter reformatted the input rather than answering it.
+// Returns the reason when the output should be discarded.
+pub fn check_formatted_output(input: &str, output: &str) -> Result<(), String> {
+    let input_tokens = tokenize(input);
+    let output_tokens = tokenize(output);
+
+    if output_tokens.is_empty() {
+        // An empty reply is expected for silence, not for real sentences
+        if input_tokens.len() >= 3 {
+            return Err("formatter returned nothing".to_string());
+        }
+        return Ok(());
+    }
+
+    // Compare on words so punctuation the formatter added doesn't matter, and
+    // skip phrases the speaker actually said
+    let input_words = format!(" {} ", input_tokens.join(" "));
+    let output_words = format!(" {} ", output_tokens.join(" "));
+    let said = |phrase: &str| input_words.contains(&format!(" {} ", phrase));
+
+    let reply_start = format!(" {} ", skip_words(&output_tokens, REPLY_FILLERS));
+    let starts_with = |pattern: &str| {
+        let pattern = format!(" {} ", pattern);
+        output_words.starts_with(&pattern) || reply_start.starts_with(&pattern)
+    };
+
+    for pattern in PREAMBLE_PATTERNS {
+        if starts_with(pattern) && !said(pattern) {
+            return Err(format!("output starts with \"{}\"", pattern));
+        }
+    }
+
+    for phrase in ASSISTANT_PHRASES {
+        if output_words.contains(&format!(" {} ", phrase)) && !said(phrase) {
+            return Err(format!("output contains \"{}\"", phrase));
+        }
+    }
+
+    let question_start = format!(" {} ", skip_words(&input_tokens, LEADING_FILLERS));
+    let asked_question = QUESTION_OPENERS
+        .iter()
+        .any(|opener| question_start.starts_with(&format!(" {} ", opener)));
+    let question_mark = input.trim_end().ends_with('?');
+    let answered = ANSWER_OPENERS.iter().any(|opener| starts_with(opener));
+    if asked_question && (question_mark || answered) && !output.contains('?') {
+        return Err("a dictated question came back as an answer".to_string());
+    }
+
+    if input_tokens.len() < GUARDRAIL_MIN_WORDS {
+        return Ok(());
+    }
+
+    let ratio = output_tokens.len() as f32 / input_tokens.len() as f32;
+    if !(MIN_LENGTH_RATIO..=MAX_LENGTH_RATIO).contains(&ratio) {
+        return Err(format!("output is {:.1}x the input length", ratio));
+    }
+
+    let input_set: HashSet<&str> = input_tokens.iter().map(|t| t.as_str()).collect();
+    let shared = output_tokens.iter().filter(|t| input_set.contains(t.as_str())).count();
+    let overlap = shared as f32 / output_tokens.len() as f32;
+    if overlap < MIN_TOKEN_OVERLAP {
+        return Err(format!("only {:.0}% of output words appear in the input", overlap * 100.0));
+    }
+
+    Ok(())
+}
+
+// Words after any leading `skip` words, joined with spaces
+fn skip_words(tokens: &[String], skip: &[&str]) -> String {
+    tokens
+        .iter()
+        .skip_while(|t| skip.contains(&t.as_str()))
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Lowercased words with surrounding punctuation stripped. Curly apostrophes are
+// unified so "Here’s" matches "here's".
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace('\u{2019}', "'")
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\''))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect()
+}
+
+fn chat_messages(system_prompt: &str, text: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: text.to_string(),
+        },
+    ]
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    temperature: f32,
+    // OpenAI's current name for the output limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+    // The older name, which is what most compatible servers understand
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+// OpenAI chat completions or a compatible server
+pub struct OpenAiFormatter {
+    client: reqwest::Client,
+    endpoint: EndpointConfig,
+    api_key: Option<String>,
+    temperature: f32,
+    max_tokens: Option<u32>,
+}
+
+#[async_trait]
+impl FormattingProvider for OpenAiFormatter {
+    fn name(&self) -> String {
+        format!("openai:{}", self.endpoint.model)
+    }
+
+    async fn format(&self, system_prompt: &str, text: &str) -> Result<String, ApiError> {
+        format_with_gpt(self, system_prompt, text).await
+    }
+}
+
+async fn format_with_gpt(
+    formatter: &OpenAiFormatter,
+    system_prompt: &str,
+    raw_text: &str,
+) -> Result<String, ApiError> {
+    let client = &formatter.client;
+    let endpoint = &formatter.endpoint;
+
+    let request = ChatRequest {
+        model: endpoint.model.clone(),
+        messages: chat_messages(system_prompt, raw_text),
+        temperature: formatter.temperature,
+        max_completion_tokens: formatter.max_tokens.filter(|_| endpoint.is_openai()),
+        max_tokens: formatter.max_tokens.filter(|_| !endpoint.is_openai()),
+    };
+
+    let response = api::send_with_retry("Chat", || {
+        Ok(endpoint
+            .apply_headers(client.post(endpoint.url("/chat/completions")), formatter.api_key.as_deref())
+            .header("Content-Type", "application/json")
+            .json(&request))
+    })
+    .await?;
+
+    let chat_response: ChatResponse = api::parse_json("Chat", response).await?;
+
+    chat_response
+        .choices
+        .first()
+        .map(|c| c.message.content.clone())
+        .ok_or_else(|| ApiError::Parse {
+            message: "No response from AI".to_string(),
+        })
+}
+
+#[derive(Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaChatResponse {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaTagsResponse {
+    models: Vec<OllamaModel>,
+}
+
+// A local Ollama server
+pub struct OllamaFormatter {
+    client: reqwest::Client,
+    endpoint: EndpointConfig,
+    temperature: f32,
+    max_tokens: Option<u32>,
+}
+
+#[async_trait]
+impl FormattingProvider for OllamaFormatter {
+    fn name(&self) -> String {
+        format!("ollama:{}", self.endpoint.model)
+    }
+
+    async fn format(&self, system_prompt: &str, text: &str) -> Result<String, ApiError> {
+        let request = OllamaChatRequest {
+            model: self.endpoint.model.clone(),
+            messages: chat_messages(system_prompt, text),
+            stream: false,
+            options: OllamaOptions {
+                temperature: self.temperature,
+                num_predict: self.max_tokens,
+            },
+        };
+
+        let response = api::send_with_retry("Ollama", || {
+            Ok(self
+                .endpoint
+                .apply_headers(
+                    self.client.post(self.endpoint.url("/api/chat")),
+                    self.endpoint.resolve_api_key(None).as_deref(),
+                )
+                .json(&request))
+        })
+        .await?;
+
+        let chat_response: OllamaChatResponse = api::parse_json("Ollama", response).await?;
+
+        Ok(chat_response.message.content)
+    }
+}
+
+// List models pulled into an Ollama server
+pub async fn list_ollama_models(client: &reqwest::Client, endpoint: &EndpointConfig) -> Result<Vec<String>, ApiError> {
+    let response = api::send_with_retry("Ollama", || {
+        Ok(endpoint.apply_headers(client.get(endpoint.url("/api/tags")), endpoint.resolve_api_key(None).as_deref()))
+    })
+    .await?;
+
+    let tags: OllamaTagsResponse = api::parse_json("Ollama", response).await?;
+
+    let mut models: Vec<String> = tags.models.into_iter().map(|m| m.name).collect();
+    models.sort();
+    Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    // Serve a canned response, returning the base URL and the server
+    async fn mock_server(status: u16, body: &str) -> (String, MockServer) {
+        let server = MockServer::start(vec![MockResponse::json(status, body)]).await;
+        (server.url.clone(), server)
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().build().unwrap()
+    }
+
+    fn endpoint(base_url: &str, model: &str) -> EndpointConfig {
+        EndpointConfig {
+            base_url: base_url.to_string(),
+            model: model.to_string(),
+            headers: HashMap::from([("X-Extra".to_string(), "yes".to_string())]),
+            api_key: None,
+        }
+    }
+
+    fn openai_formatter(base_url: &str, api_key: Option<&str>) -> OpenAiFormatter {
+        OpenAiFormatter {
+            client: client(),
+            endpoint: endpoint(base_url, "gpt-test"),
+            api_key: api_key.map(str::to_string),
+            temperature: 0.2,
+            max_tokens: Some(256),
+        }
+    }
+
+    fn ollama_formatter(base_url: &str) -> OllamaFormatter {
+        OllamaFormatter {
+            client: client(),
+            endpoint: endpoint(base_url, "llama3"),
+            temperature: 0.2,
+            max_tokens: Some(128),
+        }
+    }
+
+    #[test]
+    fn guardrail_rejects_preambles() {
+        let input = "please send the report to the team by friday";
+        assert!(check_formatted_output(input, "Sure, here's the formatted text: Please send the report.").is_err());
+        assert!(check_formatted_output(input, "Okay, here is the cleaned version.").is_err());
+        assert!(check_formatted_output(input, "Sure thing! Please send the report to the team by Friday.").is_err());
+        assert!(check_formatted_output(input, "Please send the report to the team by Friday. I hope this helps!").is_err());
+    }
+
+    #[test]
+    fn guardrail_allows_phrases_the_speaker_said() {
+        let input = "let me know if you can make it on friday";
+        assert!(check_formatted_output(input, "Let me know if you can make it on Friday.").is_ok());
+        let input = "sure thing I will send the report today";
+        assert!(check_formatted_output(input, "Sure thing, I will send the report today.").is_ok());
+    }
+
+    #[test]
+    fn guardrail_rejects_answered_questions() {
+        // A '?' in the input marks it as a question
+        assert!(check_formatted_output("what is the capital of france?", "The capital of France is Paris.").is_err());
+        assert!(check_formatted_output("um so how do I reset my password?", "Open settings and click reset.").is_err());
+        // Without one, a reply-style output gives the answer away
+        assert!(check_formatted_output("what is the capital of france", "It's Paris.").is_err());
+        assert!(check_formatted_output("is there a way to undo this", "Yes, press Ctrl+Z to undo.").is_err());
+        assert!(check_formatted_output("how do I reset my password", "You can reset it from the settings page.").is_err());
+    }
+
+    #[test]
+    fn guardrail_allows_formatted_questions_and_headings() {
+        assert!(check_formatted_output("what is the capital of france?", "What is the capital of France?").is_ok());
+        assert!(check_formatted_output("um so how do I reset my password", "How do I reset my password?").is_ok());
+        // Question openers are common in headings and statements
+        assert!(check_formatted_output("How to configure the proxy", "How to configure the proxy").is_ok());
+        assert!(check_formatted_output("how to configure the proxy", "## How to Configure the Proxy").is_ok());
+        assert!(check_formatted_output("what is new in this release", "What is new in this release:").is_ok());
+    }
+
+    #[tokio::test]
+    async fn chat_request_and_response_shape() {
+        let (url, server) = mock_server(200, r#"{"choices":[{"message":{"role":"assistant","content":"Hello, world."}}]}"#).await;
+
+        let result = openai_formatter(&url, Some("sk-test")).format("Fix it", "hello world").await;
+        let captured = server.requests().remove(0);
+
+        assert_eq!(result.unwrap(), "Hello, world.");
+        assert_eq!(captured.request_line, "POST /chat/completions HTTP/1.1");
+        assert_eq!(captured.headers.get("authorization").map(String::as_str), Some("Bearer sk-test"));
+        assert_eq!(captured.headers.get("x-extra").map(String::as_str), Some("yes"));
+        assert_eq!(captured.body["model"], "gpt-test");
+        assert_eq!(
+            captured.body["messages"],
+            json!([
+                {"role": "system", "content": "Fix it"},
+                {"role": "user", "content": "hello world"},
+            ])
+        );
+        assert!((captured.body["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+        // Only api.openai.com gets max_completion_tokens
+        assert_eq!(captured.body["max_tokens"], 256);
+        assert!(captured.body.get("max_completion_tokens").is_none());
+    }
+
+    #[tokio::test]
+    async fn chat_without_key_sends_no_auth() {
+        let (url, server) = mock_server(200, r#"{"choices":[{"message":{"role":"assistant","content":"ok"}}]}"#).await;
+
+        openai_formatter(&url, None).format("p", "t").await.unwrap();
+        let captured = server.requests().remove(0);
+
+        assert!(!captured.headers.contains_key("authorization"));
+    }
+
+    #[tokio::test]
+    async fn chat_maps_unauthorized_to_auth() {
+        let (url, server) = mock_server(401, r#"{"error":{"message":"Incorrect API key provided","code":"invalid_api_key"}}"#).await;
+
+        let error = openai_formatter(&url, Some("bad")).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        assert!(matches!(error, ApiError::Auth { .. }), "{:?}", error);
+        assert!(error.message().contains("Incorrect API key provided"));
+    }
+
+    #[tokio::test]
+    async fn chat_maps_insufficient_quota_to_quota_exceeded() {
+        let (url, server) =
+            mock_server(429, r#"{"error":{"message":"You exceeded your current quota","code":"insufficient_quota"}}"#).await;
+
+        let error = openai_formatter(&url, Some("sk")).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        assert!(matches!(error, ApiError::QuotaExceeded { .. }), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn chat_maps_unknown_model_to_request() {
+        let (url, server) = mock_server(404, r#"{"error":{"message":"The model `gpt-test` does not exist"}}"#).await;
+
+        let error = openai_formatter(&url, Some("sk")).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        match error {
+            ApiError::Request { status, message } => {
+                assert_eq!(status, 404);
+                assert!(message.contains("does not exist"), "{}", message);
+            }
+            other => panic!("expected Request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn chat_rejects_empty_choices() {
+        let (url, server) = mock_server(200, r#"{"choices":[]}"#).await;
+
+        let error = openai_formatter(&url, None).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        assert!(matches!(error, ApiError::Parse { .. }), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn chat_rejects_malformed_response() {
+        let (url, server) = mock_server(200, r#"{"unexpected":true}"#).await;
+
+        let error = openai_formatter(&url, None).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        assert!(matches!(error, ApiError::Parse { .. }), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn ollama_request_and_response_shape() {
+        let (url, server) = mock_server(200, r#"{"model":"llama3","message":{"role":"assistant","content":"Tidy text."},"done":true}"#).await;
+
+        let result = ollama_formatter(&url).format("Fix it", "tidy text").await;
+        let captured = server.requests().remove(0);
+
+        assert_eq!(result.unwrap(), "Tidy text.");
+        assert_eq!(captured.request_line, "POST /api/chat HTTP/1.1");
+        assert!(!captured.headers.contains_key("authorization"));
+        assert_eq!(captured.body["model"], "llama3");
+        assert_eq!(captured.body["stream"], false);
+        assert_eq!(captured.body["messages"][1], json!({"role": "user", "content": "tidy text"}));
+        assert_eq!(captured.body["options"]["num_predict"], 128);
+        assert!(captured.body.get("max_tokens").is_none());
+    }
+
+    #[tokio::test]
+    async fn ollama_maps_missing_model_to_request() {
+        let (url, server) = mock_server(404, r#"{"error":"model \"llama3\" not found, try pulling it first"}"#).await;
+
+        let error = ollama_formatter(&url).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        match error {
+            ApiError::Request { status, message } => {
+                assert_eq!(status, 404);
+                assert!(message.contains("try pulling it first"), "{}", message);
+            }
+            other => panic!("expected Request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn ollama_rejects_malformed_response() {
+        let (url, server) = mock_server(200, r#"{"done":true}"#).await;
+
+        let error = ollama_formatter(&url).format("p", "t").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+
+        assert!(matches!(error, ApiError::Parse { .. }), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_is_network_error() {
+        // Bind then drop a listener so the port is known to be closed
+        let url = {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+
+        let error = ollama_formatter(&url).format("p", "t").await.unwrap_err();
+
+        assert!(matches!(error, ApiError::Network { .. }), "{:?}", error);
+    }
+}
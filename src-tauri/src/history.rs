@@ -0,0 +1,350 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+// Schema steps, applied in order. PRAGMA user_version records how many have run.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE transcriptions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        created_at INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        raw_text TEXT NOT NULL,
+        formatted_text TEXT NOT NULL,
+        profile TEXT NOT NULL,
+        provider TEXT NOT NULL
+    );
+    CREATE INDEX transcriptions_created_at ON transcriptions (created_at);
+
+    -- Full-text index kept in sync with the table by triggers
+    CREATE VIRTUAL TABLE transcriptions_fts USING fts5(
+        raw_text,
+        formatted_text,
+        content = 'transcriptions',
+        content_rowid = 'id'
+    );
+    CREATE TRIGGER transcriptions_ai AFTER INSERT ON transcriptions BEGIN
+        INSERT INTO transcriptions_fts (rowid, raw_text, formatted_text)
+        VALUES (new.id, new.raw_text, new.formatted_text);
+    END;
+    CREATE TRIGGER transcriptions_ad AFTER DELETE ON transcriptions BEGIN
+        INSERT INTO transcriptions_fts (transcriptions_fts, rowid, raw_text, formatted_text)
+        VALUES ('delete', old.id, old.raw_text, old.formatted_text);
+    END;
+"#,
+    // Link to the cached audio, so an entry can be re-transcribed
+    "ALTER TABLE transcriptions ADD COLUMN recording_id TEXT;",
+];
+
+// State holding the history database, opened during app setup
+#[derive(Default)]
+pub struct HistoryState {
+    conn: Mutex<Option<Connection>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct HistoryEntry {
+    pub id: i64,
+    // Unix time in milliseconds
+    pub created_at: i64,
+    pub duration_ms: i64,
+    pub raw_text: String,
+    pub formatted_text: String,
+    // Formatter used, e.g. "openai:gpt-5.2", or "raw" when formatting was skipped
+    pub profile: String,
+    // Transcription provider, e.g. "openai:whisper-1"
+    pub provider: String,
+    // Cached audio for this entry; None once it has been evicted or if caching was off
+    pub recording_id: Option<String>,
+}
+
+// Fields for a new history row; id and timestamp are assigned on insert
+pub struct NewHistoryEntry {
+    pub duration_ms: i64,
+    pub raw_text: String,
+    pub formatted_text: String,
+    pub profile: String,
+    pub provider: String,
+    pub recording_id: Option<String>,
+}
+
+impl HistoryState {
+    pub fn open(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open history database: {}", e))?;
+        migrate(&conn)?;
+
+        let mut guard = self.conn.lock().map_err(|e| e.to_string())?;
+        *guard = Some(conn);
+        Ok(())
+    }
+
+    fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+        let guard = self.conn.lock().map_err(|e| e.to_string())?;
+        let conn = guard.as_ref().ok_or("History database is not available")?;
+        f(conn).map_err(|e| format!("History database error: {}", e))
+    }
+
+    pub fn insert(&self, entry: NewHistoryEntry) -> Result<i64, String> {
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO transcriptions (created_at, duration_ms, raw_text, formatted_text, profile, provider, recording_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    created_at,
+                    entry.duration_ms,
+                    entry.raw_text,
+                    entry.formatted_text,
+                    entry.profile,
+                    entry.provider,
+                    entry.recording_id,
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+    }
+
+    pub fn list(&self, limit: u32, offset: u32) -> Result<Vec<HistoryEntry>, String> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, created_at, duration_ms, raw_text, formatted_text, profile, provider, recording_id
+                 FROM transcriptions ORDER BY created_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            )?;
+            let rows = stmt.query_map(params![limit, offset], row_to_entry)?;
+            rows.collect()
+        })
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<HistoryEntry>, String> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT id, created_at, duration_ms, raw_text, formatted_text, profile, provider, recording_id
+                 FROM transcriptions WHERE id = ?1",
+                params![id],
+                row_to_entry,
+            )
+            .optional()
+        })
+    }
+
+    pub fn delete(&self, id: i64) -> Result<bool, String> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute("DELETE FROM transcriptions WHERE id = ?1", params![id])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    // Detach evicted recordings from their entries so the UI stops offering a re-run
+    pub fn clear_recordings(&self, recording_ids: &[String]) -> Result<(), String> {
+        if recording_ids.is_empty() {
+            return Ok(());
+        }
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut stmt = tx.prepare("UPDATE transcriptions SET recording_id = NULL WHERE recording_id = ?1")?;
+                for id in recording_ids {
+                    stmt.execute(params![id])?;
+                }
+            }
+            tx.commit()
+        })
+    }
+
+    // Full-text search over raw and formatted text, best matches first
+    pub fn search(&self, query: &str, limit: u32) -> Result<Vec<HistoryEntry>, String> {
+        let match_query = fts_query(query);
+        if match_query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT t.id, t.created_at, t.duration_ms, t.raw_text, t.formatted_text, t.profile, t.provider, t.recording_id
+                 FROM transcriptions_fts
+                 JOIN transcriptions t ON t.id = transcriptions_fts.rowid
+                 WHERE transcriptions_fts MATCH ?1
+                 ORDER BY transcriptions_fts.rank LIMIT ?2",
+            )?;
+            let rows = stmt.query_map(params![match_query, limit], row_to_entry)?;
+            rows.collect()
+        })
+    }
+}
+
+fn migrate(conn: &Connection) -> Result<(), String> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration,
+            index + 1
+        ))
+        .map_err(|e| format!("History migration {} failed: {}", index + 1, e))?;
+    }
+    Ok(())
+}
+
+fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        duration_ms: row.get(2)?,
+        raw_text: row.get(3)?,
+        formatted_text: row.get(4)?,
+        profile: row.get(5)?,
+        provider: row.get(6)?,
+        recording_id: row.get(7)?,
+    })
+}
+
+// Turn free text into an FTS5 query: every word must match, the last one as a prefix.
+// Words are quoted so punctuation and FTS operators in the input can't break the query.
+fn fts_query(query: &str) -> String {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    match words.split_last() {
+        Some((last, [])) => format!("{}*", last),
+        Some((last, rest)) => format!("{} {}*", rest.join(" "), last),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_history() -> HistoryState {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        HistoryState {
+            conn: Mutex::new(Some(conn)),
+        }
+    }
+
+    fn entry(raw_text: &str, formatted_text: &str) -> NewHistoryEntry {
+        NewHistoryEntry {
+            duration_ms: 1200,
+            raw_text: raw_text.to_string(),
+            formatted_text: formatted_text.to_string(),
+            profile: "raw".to_string(),
+            provider: "openai:whisper-1".to_string(),
+            recording_id: Some("1700000000000".to_string()),
+        }
+    }
+
+    fn ids(entries: &[HistoryEntry]) -> Vec<i64> {
+        entries.iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn fts_query_quotes_words_and_prefixes_the_last() {
+        assert_eq!(fts_query("deploy"), "\"deploy\"*");
+        assert_eq!(fts_query("  deploy   the app "), "\"deploy\" \"the\" \"app\"*");
+        assert_eq!(fts_query("\"foo\" OR bar*"), "\"\"\"foo\"\"\" \"OR\" \"bar*\"*");
+        assert_eq!(fts_query("   "), "");
+    }
+
+    #[test]
+    fn insert_list_get_and_delete() {
+        let history = memory_history();
+        let first = history.insert(entry("first take", "First take.")).unwrap();
+        let second = history.insert(entry("second take", "Second take.")).unwrap();
+
+        assert_eq!(ids(&history.list(10, 0).unwrap()), [second, first]);
+        assert_eq!(ids(&history.list(1, 1).unwrap()), [first]);
+
+        let stored = history.get(first).unwrap().unwrap();
+        assert_eq!(stored.raw_text, "first take");
+        assert_eq!(stored.formatted_text, "First take.");
+        assert_eq!(stored.duration_ms, 1200);
+        assert_eq!(stored.recording_id.as_deref(), Some("1700000000000"));
+
+        assert!(history.delete(first).unwrap());
+        assert!(!history.delete(first).unwrap());
+        assert!(history.get(first).unwrap().is_none());
+        assert_eq!(ids(&history.list(10, 0).unwrap()), [second]);
+    }
+
+    #[test]
+    fn search_matches_words_and_prefixes() {
+        let history = memory_history();
+        let deploy = history.insert(entry("we should deploy on friday", "We should deploy on Friday.")).unwrap();
+        let standup = history.insert(entry("standup notes", "Standup notes: deployment went fine.")).unwrap();
+
+        assert_eq!(ids(&history.search("friday", 10).unwrap()), [deploy]);
+        // The last word is a prefix, earlier words must match whole
+        let mut found = ids(&history.search("deploy", 10).unwrap());
+        found.sort();
+        assert_eq!(found, [deploy, standup]);
+        assert_eq!(ids(&history.search("notes deploy", 10).unwrap()), [standup]);
+        assert!(history.search("deploy notes", 10).unwrap().is_empty());
+        assert!(history.search("  ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_treats_operators_as_text() {
+        let history = memory_history();
+        let quoted = history.insert(entry("say \"foo\" OR bar", "Say \"foo\" or bar.")).unwrap();
+        history.insert(entry("just bar", "Just bar.")).unwrap();
+
+        // Without quoting this would be a syntax error or match "bar" alone
+        assert_eq!(ids(&history.search("\"foo\" OR bar*", 10).unwrap()), [quoted]);
+        assert!(history.search("NEAR( AND", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn deleted_entries_leave_the_search_index() {
+        let history = memory_history();
+        let id = history.insert(entry("delete me later", "Delete me later.")).unwrap();
+        assert_eq!(ids(&history.search("later", 10).unwrap()), [id]);
+
+        history.delete(id).unwrap();
+        assert!(history.search("later", 10).unwrap().is_empty());
+        history
+            .with_conn(|conn| conn.execute("INSERT INTO transcriptions_fts (transcriptions_fts) VALUES ('integrity-check')", []))
+            .unwrap();
+    }
+
+    #[test]
+    fn migrations_step_from_the_stored_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!("{} PRAGMA user_version = 1;", MIGRATIONS[0])).unwrap();
+        conn.execute(
+            "INSERT INTO transcriptions (created_at, duration_ms, raw_text, formatted_text, profile, provider)
+             VALUES (1, 500, 'old entry', 'Old entry.', 'raw', 'openai:whisper-1')",
+            [],
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        // Running again at the latest version changes nothing
+        migrate(&conn).unwrap();
+
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        let history = HistoryState {
+            conn: Mutex::new(Some(conn)),
+        };
+        let entries = history.list(10, 0).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].raw_text, "old entry");
+        assert_eq!(entries[0].recording_id, None);
+        assert_eq!(entries.len(), history.search("old", 10).unwrap().len());
+    }
+}
@@ -0,0 +1,358 @@
+use arboard::{Clipboard, ImageData};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::Settings;
+
+// Wait for focus to return to the previous app before pressing keys
+const FOCUS_DELAY: Duration = Duration::from_millis(150);
+
+// How transcripts are inserted into the focused app
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionMode {
+    // Copy to the clipboard and press Ctrl+V (Cmd+V on macOS)
+    #[default]
+    Paste,
+    // Type the text as keystrokes, for apps where Ctrl+V doesn't paste
+    Type,
+}
+
+// How text gets into the focused app, taken from settings
+#[derive(Clone, Copy, Debug)]
+pub struct InjectOptions {
+    pub mode: InjectionMode,
+    // Pause between characters in type mode; zero types the whole text at once
+    pub typing_delay: Duration,
+    // Put the user's clipboard back after pasting
+    pub preserve_clipboard: bool,
+    // Time the target app gets to read the pasted text before the clipboard is restored
+    pub restore_delay: Duration,
+}
+
+impl InjectOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            mode: settings.injection_mode,
+            typing_delay: Duration::from_millis(settings.typing_delay_ms),
+            preserve_clipboard: settings.preserve_clipboard,
+            restore_delay: Duration::from_millis(settings.clipboard_restore_delay_ms),
+        }
+    }
+}
+
+// Why text couldn't be inserted into the focused app
+#[derive(Debug, Clone, Serialize)]
+pub struct InjectionError {
+    pub message: String,
+    // The text was left on the clipboard so the user can paste it by hand
+    pub copied: bool,
+}
+
+impl fmt::Display for InjectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for InjectionError {
+    fn from(message: String) -> Self {
+        InjectionError { message, copied: false }
+    }
+}
+
+// Payload of the `injection-result` event sent after auto-paste
+#[derive(Debug, Clone, Serialize)]
+pub struct InjectionResult {
+    pub success: bool,
+    pub error: Option<InjectionError>,
+}
+
+impl From<Result<(), InjectionError>> for InjectionResult {
+    fn from(result: Result<(), InjectionError>) -> Self {
+        InjectionResult {
+            success: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+// What was on the clipboard before we replaced it
+enum ClipboardSnapshot {
+    Text(String),
+    Image(ImageData<'static>),
+    // Neither text nor an image: empty, or content arboard can't read (files, rich
+    // text without a plain-text version). The two look the same, so restoring
+    // leaves the transcript in place rather than clearing something we couldn't save.
+    Unreadable,
+}
+
+impl ClipboardSnapshot {
+    fn take(clipboard: &mut Clipboard) -> Self {
+        if let Ok(text) = clipboard.get_text() {
+            return ClipboardSnapshot::Text(text);
+        }
+        match clipboard.get_image() {
+            Ok(image) => ClipboardSnapshot::Image(image),
+            Err(_) => ClipboardSnapshot::Unreadable,
+        }
+    }
+
+    fn restore(self, clipboard: &mut Clipboard) -> Result<(), arboard::Error> {
+        match self {
+            ClipboardSnapshot::Text(text) => clipboard.set_text(text),
+            ClipboardSnapshot::Image(image) => clipboard.set_image(image),
+            ClipboardSnapshot::Unreadable => Ok(()),
+        }
+    }
+}
+
+// Insert text into the focused app, returning once the keystrokes have been sent.
+// Blocks for at least FOCUS_DELAY, so call it from a blocking thread. On failure
+// the text is put on the clipboard for pasting by hand.
+pub fn inject(text: &str, options: InjectOptions) -> Result<(), InjectionError> {
+    let result = available_senders().and_then(|senders| {
+        match options.mode {
+            InjectionMode::Paste => paste_text(text, options, &senders),
+            InjectionMode::Type => {
+                // Give the Tauri window time to lose focus first
+                std::thread::sleep(FOCUS_DELAY);
+                // Typing is only retried with another sender if nothing was typed yet
+                first_working(&senders, |sender| sender.type_text(text, options.typing_delay))
+                    .map_err(|e| format!("Failed to type text: {}", e))
+            }
+        }
+    });
+
+    result.map_err(|message| {
+        let copied = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)).is_ok();
+        InjectionError { message, copied }
+    })
+}
+
+// Copy text to the clipboard and simulate a paste. The previous contents are
+// restored in the background, so this doesn't wait out the restore delay.
+fn paste_text(text: &str, options: InjectOptions, senders: &[KeySender]) -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|e| format!("Clipboard error: {}", e))?;
+    let snapshot = options
+        .preserve_clipboard
+        .then(|| ClipboardSnapshot::take(&mut clipboard))
+        .filter(|snapshot| !matches!(snapshot, ClipboardSnapshot::Unreadable));
+    clipboard.set_text(text).map_err(|e| format!("Failed to set clipboard: {}", e))?;
+
+    // Give the Tauri window time to lose focus first
+    std::thread::sleep(FOCUS_DELAY);
+    // The clipboard isn't restored on failure, the transcript stays there to paste by hand
+    // A failed paste can be retried with the next sender: the clipboard still holds the text
+    first_working(senders, |sender| sender.paste().map_err(SendError::retryable))
+        .map_err(|e| format!("Failed to paste: {}", e))?;
+
+    if let Some(snapshot) = snapshot {
+        let text = text.to_string();
+        std::thread::spawn(move || {
+            std::thread::sleep(options.restore_delay);
+            restore_clipboard(&mut clipboard, &text, snapshot);
+        });
+    }
+    Ok(())
+}
+
+// Put the snapshot back, unless something else was copied while we waited
+fn restore_clipboard(clipboard: &mut Clipboard, text: &str, snapshot: ClipboardSnapshot) {
+    match clipboard.get_text() {
+        Ok(current) if current == text => {}
+        _ => {
+            println!("Clipboard changed since pasting, not restoring it");
+            return;
+        }
+    }
+
+    if let Err(e) = snapshot.restore(clipboard) {
+        eprintln!("Failed to restore clipboard: {}", e);
+    }
+}
+
+// Why a sender failed
+enum SendError {
+    // Nothing was sent, e.g. the tool isn't installed or the compositor refused it
+    NotStarted(String),
+    // Some keystrokes may already have reached the app
+    Interrupted(String),
+}
+
+impl SendError {
+    fn retryable(self) -> Self {
+        match self {
+            SendError::Interrupted(message) => SendError::NotStarted(message),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::NotStarted(message) | SendError::Interrupted(message) => f.write_str(message),
+        }
+    }
+}
+
+// Ways of sending keystrokes to the focused app
+#[derive(Clone, Copy, Debug)]
+enum KeySender {
+    // Native APIs on Windows and macOS, XTest on X11
+    Enigo,
+    // Wayland virtual-keyboard protocol (wlroots compositors, KDE)
+    Wtype,
+    // Kernel uinput device; works on any compositor but needs ydotoold running
+    Ydotool,
+}
+
+impl KeySender {
+    fn name(self) -> &'static str {
+        match self {
+            KeySender::Enigo => "enigo",
+            KeySender::Wtype => "wtype",
+            KeySender::Ydotool => "ydotool",
+        }
+    }
+
+    fn paste(self) -> Result<(), SendError> {
+        match self {
+            KeySender::Enigo => enigo_paste(),
+            KeySender::Wtype => run(self.name(), &["-M", "ctrl", "-k", "v", "-m", "ctrl"]),
+            // Linux input event codes: 29 is left Ctrl, 47 is V
+            KeySender::Ydotool => run(self.name(), &["key", "29:1", "47:1", "47:0", "29:0"]),
+        }
+    }
+
+    fn type_text(self, text: &str, typing_delay: Duration) -> Result<(), SendError> {
+        let delay_ms = typing_delay.as_millis().to_string();
+        match self {
+            KeySender::Enigo => enigo_type(text, typing_delay),
+            KeySender::Wtype => {
+                // wtype fails on compositors without the virtual-keyboard protocol; a
+                // bare sleep checks that without typing anything
+                run(self.name(), &["-s", "0"]).map_err(SendError::retryable)?;
+                run(self.name(), &["-d", &delay_ms, "--", text])
+            }
+            KeySender::Ydotool => run(self.name(), &["type", "--key-delay", &delay_ms, "--", text]),
+        }
+    }
+}
+
+// Senders to try, in order. enigo can't send keystrokes on Wayland, where most
+// compositors silently drop them, so Wayland sessions use the external tools instead.
+fn available_senders() -> Result<Vec<KeySender>, String> {
+    if !is_wayland_session() {
+        return Ok(vec![KeySender::Enigo]);
+    }
+
+    let senders: Vec<KeySender> = [KeySender::Wtype, KeySender::Ydotool]
+        .into_iter()
+        .filter(|sender| on_path(sender.name()))
+        .collect();
+    if senders.is_empty() {
+        return Err("Can't send keystrokes on Wayland. Install wtype or ydotool to enable auto-paste.".to_string());
+    }
+    Ok(senders)
+}
+
+#[cfg(target_os = "linux")]
+fn is_wayland_session() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session.eq_ignore_ascii_case("wayland"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_wayland_session() -> bool {
+    false
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+// Run `action` with each sender until one succeeds. wtype fails straight away on
+// compositors without the virtual-keyboard protocol (GNOME), so ydotool gets a turn.
+// Stops at the first sender that may have sent input, so text is never typed twice.
+fn first_working(senders: &[KeySender], action: impl Fn(KeySender) -> Result<(), SendError>) -> Result<(), String> {
+    let mut errors = Vec::new();
+    for &sender in senders {
+        match action(sender) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                errors.push(format!("{}: {}", sender.name(), e));
+                if let SendError::Interrupted(_) = e {
+                    break;
+                }
+            }
+        }
+    }
+    Err(errors.join("; "))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), SendError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| SendError::NotStarted(format!("failed to run: {}", e)))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(SendError::Interrupted(match stderr.trim() {
+        "" => format!("exited with {}", output.status),
+        message => message.to_string(),
+    }))
+}
+
+fn enigo_paste() -> Result<(), SendError> {
+    use enigo::{Enigo, Settings};
+
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| SendError::NotStarted(e.to_string()))?;
+    enigo_paste_keys(&mut enigo).map_err(SendError::Interrupted)
+}
+
+fn enigo_paste_keys(enigo: &mut enigo::Enigo) -> Result<(), String> {
+    use enigo::{Direction, Key, Keyboard};
+
+    // Cmd+V on macOS, Ctrl+V on Windows/Linux
+    #[cfg(target_os = "macos")]
+    let modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Key::Control;
+
+    enigo.key(modifier, Direction::Press).map_err(|e| e.to_string())?;
+    let result = enigo.key(Key::Unicode('v'), Direction::Click);
+    // Always release the modifier, even if the V didn't go through
+    enigo.key(modifier, Direction::Release).map_err(|e| e.to_string())?;
+    result.map_err(|e| e.to_string())
+}
+
+fn enigo_type(text: &str, typing_delay: Duration) -> Result<(), SendError> {
+    use enigo::{Enigo, Settings};
+
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| SendError::NotStarted(e.to_string()))?;
+    enigo_type_keys(&mut enigo, text, typing_delay).map_err(SendError::Interrupted)
+}
+
+fn enigo_type_keys(enigo: &mut enigo::Enigo, text: &str, typing_delay: Duration) -> Result<(), String> {
+    use enigo::Keyboard;
+
+    if typing_delay.is_zero() {
+        return enigo.text(text).map_err(|e| e.to_string());
+    }
+
+    let mut buf = [0u8; 4];
+    for c in text.chars() {
+        enigo.text(c.encode_utf8(&mut buf)).map_err(|e| e.to_string())?;
+        std::thread::sleep(typing_delay);
+    }
+    Ok(())
+}
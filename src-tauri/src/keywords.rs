@@ -0,0 +1,1030 @@
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Where a keyword may match
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    // Only as a whole word, so "ai" leaves "paid" alone
+    #[default]
+    WholeWord,
+    // Anywhere, including inside longer words
+    Substring,
+    // `spoken` is a regular expression and the replacement may use $1, $2, ...
+    Regex,
+}
+
+// Replace what the transcriber heard (`spoken`) with the user's spelling
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeywordRule {
+    pub spoken: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    // Follow the capitalization of the matched text, e.g. "Colour" -> "Color"
+    #[serde(default)]
+    pub preserve_case: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl KeywordRule {
+    // Trim and lowercase literal keywords; matching ignores case anyway. Patterns are
+    // kept as typed since case changes their meaning.
+    pub fn normalized(mut self) -> Self {
+        if self.match_mode != MatchMode::Regex {
+            self.spoken = self.spoken.trim().to_lowercase();
+        }
+        self
+    }
+
+    // Whether this is the rule for `spoken` in `match_mode`; a whole-word and a
+    // substring rule for the same text are separate rules. Regex patterns are
+    // compared exactly since case matters there (\d vs \D).
+    pub fn is_for(&self, match_mode: MatchMode, spoken: &str) -> bool {
+        if self.match_mode != match_mode {
+            return false;
+        }
+        match match_mode {
+            MatchMode::Regex => self.spoken == spoken,
+            _ => self.spoken.to_lowercase() == spoken.to_lowercase(),
+        }
+    }
+
+    // Check that the rule can be applied, returning a message for the user if not
+    pub fn validate(&self) -> Result<(), String> {
+        if self.spoken.trim().is_empty() {
+            return Err("Spoken text can't be empty".to_string());
+        }
+        if self.match_mode != MatchMode::Regex {
+            return Ok(());
+        }
+
+        let regex = build_regex(&self.spoken).map_err(|e| format!("Invalid pattern: {}", e))?;
+        // All rules share one regex, where the same group name twice is an error
+        if regex.capture_names().flatten().next().is_some() {
+            return Err("Named groups aren't supported, use numbered groups ($1, $2, ...)".to_string());
+        }
+        if regex.is_match("") {
+            return Err("Pattern must not match empty text".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Read keywords as stored in settings. Older versions stored a
+// {"spoken": "replacement"} map, which becomes whole-word rules in a stable order.
+// Entries are read one by one so a single bad one doesn't lose the rest.
+pub fn from_stored(value: &serde_json::Value) -> Vec<KeywordRule> {
+    let keep = |rule: Result<KeywordRule, String>| {
+        rule.map_err(|e| eprintln!("Skipping stored keyword rule: {}", e)).ok()
+    };
+
+    match value {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| serde_json::from_value(item.clone()).map_err(|e| e.to_string()))
+            .filter_map(keep)
+            .collect(),
+        serde_json::Value::Object(map) => {
+            let mut rules: Vec<KeywordRule> = map
+                .iter()
+                .map(|(spoken, replacement)| legacy_rule(spoken, replacement))
+                .filter_map(keep)
+                .collect();
+            rules.sort_by(|a, b| a.spoken.cmp(&b.spoken));
+            rules
+        }
+        _ => Vec::new(),
+    }
+}
+
+// One entry of the old {"spoken": "replacement"} map
+fn legacy_rule(spoken: &str, replacement: &serde_json::Value) -> Result<KeywordRule, String> {
+    let replacement = replacement
+        .as_str()
+        .ok_or_else(|| format!("Replacement for \"{}\" isn't text", spoken))?;
+    Ok(KeywordRule {
+        spoken: spoken.to_string(),
+        replacement: replacement.to_string(),
+        match_mode: MatchMode::default(),
+        preserve_case: false,
+        enabled: true,
+    })
+}
+
+// File format for importing and exporting keyword lists
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeywordFormat {
+    // spoken,replacement[,match_mode,preserve_case,enabled] with an optional header row
+    Csv,
+    // An array of rules, or the old {"spoken": "replacement"} map
+    Json,
+}
+
+impl KeywordFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            KeywordFormat::Csv => "csv",
+            KeywordFormat::Json => "json",
+        }
+    }
+
+    // Guess from the content when the file extension didn't say
+    fn detect(content: &str) -> Self {
+        match content.trim_start_matches('\u{feff}').trim_start().chars().next() {
+            Some('[') | Some('{') => KeywordFormat::Json,
+            _ => KeywordFormat::Csv,
+        }
+    }
+}
+
+// What to do with the existing rules when importing
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    // Keep existing rules; imported rules win where both have the same match mode and spoken text
+    #[default]
+    Merge,
+    // Drop existing rules and use the imported ones
+    Replace,
+}
+
+// An imported rule that replaced a different existing rule for the same match mode and spoken text
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportConflict {
+    pub spoken: String,
+    pub existing: String,
+    pub imported: String,
+}
+
+// A row that was skipped. `row` is the CSV line or the position in the JSON array.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportIssue {
+    pub row: usize,
+    pub spoken: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    // False when a replace was held back until the user confirms it
+    pub applied: bool,
+    pub added: usize,
+    pub updated: usize,
+    // Already present with the same replacement and options
+    pub unchanged: usize,
+    // Existing rules dropped because the file doesn't have them; only when replacing
+    pub removed: usize,
+    pub conflicts: Vec<ImportConflict>,
+    // Later rows for a match mode and spoken text that appeared earlier in the file; the first one is used
+    pub duplicates: Vec<ImportIssue>,
+    pub invalid: Vec<ImportIssue>,
+}
+
+// Import rules from `content` into `rules`. Fails without changing anything if the
+// file can't be parsed at all; bad rows are skipped and listed in the report. A
+// replace that would leave no rules or skip rows is only applied with `force`, so a
+// wrong file can't silently wipe the list.
+pub fn import(
+    rules: &mut Vec<KeywordRule>,
+    content: &str,
+    format: Option<KeywordFormat>,
+    mode: ImportMode,
+    force: bool,
+) -> Result<ImportReport, String> {
+    let format = format.unwrap_or_else(|| KeywordFormat::detect(content));
+    let rows = match format {
+        KeywordFormat::Csv => parse_csv_rules(content)?,
+        KeywordFormat::Json => parse_json_rules(content)?,
+    };
+
+    let mut report = ImportReport::default();
+    let mut imported: Vec<KeywordRule> = Vec::new();
+    for (row, parsed) in rows {
+        let rule = match parsed {
+            Ok(rule) => rule.normalized(),
+            Err(message) => {
+                report.invalid.push(ImportIssue { row, spoken: None, message });
+                continue;
+            }
+        };
+        if let Err(message) = rule.validate() {
+            report.invalid.push(ImportIssue {
+                row,
+                spoken: Some(rule.spoken),
+                message,
+            });
+            continue;
+        }
+
+        if imported.iter().any(|r| r.is_for(rule.match_mode, &rule.spoken)) {
+            report.duplicates.push(ImportIssue {
+                row,
+                spoken: Some(rule.spoken.clone()),
+                message: "Already defined earlier in the file".to_string(),
+            });
+            continue;
+        }
+        imported.push(rule);
+    }
+
+    // Compare against the current rules in both modes so a replace reports what changes
+    let mut result = match mode {
+        ImportMode::Merge => rules.clone(),
+        ImportMode::Replace => Vec::new(),
+    };
+    for rule in imported {
+        match rules.iter().find(|r| r.is_for(rule.match_mode, &rule.spoken)) {
+            Some(existing) if same_rule(existing, &rule) => report.unchanged += 1,
+            Some(existing) => {
+                if existing.replacement != rule.replacement {
+                    report.conflicts.push(ImportConflict {
+                        spoken: rule.spoken.clone(),
+                        existing: existing.replacement.clone(),
+                        imported: rule.replacement.clone(),
+                    });
+                }
+                report.updated += 1;
+            }
+            None => report.added += 1,
+        }
+        upsert(&mut result, rule);
+    }
+
+    if mode == ImportMode::Replace {
+        report.removed = rules
+            .iter()
+            .filter(|r| !result.iter().any(|i| i.is_for(r.match_mode, &r.spoken)))
+            .count();
+        if !force && (result.is_empty() || !report.invalid.is_empty()) {
+            return Ok(report);
+        }
+    }
+
+    *rules = result;
+    report.applied = true;
+    Ok(report)
+}
+
+pub fn export(rules: &[KeywordRule], format: KeywordFormat) -> Result<String, String> {
+    match format {
+        KeywordFormat::Json => serde_json::to_string_pretty(rules).map_err(|e| e.to_string()),
+        KeywordFormat::Csv => {
+            let mut csv = String::from("spoken,replacement,match_mode,preserve_case,enabled\r\n");
+            for rule in rules {
+                let fields = [
+                    csv_field(&rule.spoken),
+                    csv_field(&rule.replacement),
+                    match_mode_name(rule.match_mode).to_string(),
+                    rule.preserve_case.to_string(),
+                    rule.enabled.to_string(),
+                ];
+                csv.push_str(&fields.join(","));
+                csv.push_str("\r\n");
+            }
+            Ok(csv)
+        }
+    }
+}
+
+fn same_rule(a: &KeywordRule, b: &KeywordRule) -> bool {
+    a.spoken == b.spoken
+        && a.replacement == b.replacement
+        && a.match_mode == b.match_mode
+        && a.preserve_case == b.preserve_case
+        && a.enabled == b.enabled
+}
+
+type ParsedRows = Vec<(usize, Result<KeywordRule, String>)>;
+
+fn parse_json_rules(content: &str) -> Result<ParsedRows, String> {
+    let value: serde_json::Value = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    match value {
+        serde_json::Value::Array(items) => Ok(items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| (i + 1, serde_json::from_value(item).map_err(|e| e.to_string())))
+            .collect()),
+        // Only the old {"spoken": "replacement"} map; any other object is the wrong file
+        serde_json::Value::Object(map) => {
+            let mut rules = map
+                .iter()
+                .map(|(spoken, replacement)| legacy_rule(spoken, replacement))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Expected a list of keyword rules: {}", e))?;
+            rules.sort_by(|a, b| a.spoken.cmp(&b.spoken));
+            Ok(rules.into_iter().enumerate().map(|(i, rule)| (i + 1, Ok(rule))).collect())
+        }
+        _ => Err("Expected a list of keyword rules".to_string()),
+    }
+}
+
+const CSV_COLUMNS: [&str; 5] = ["spoken", "replacement", "match_mode", "preserve_case", "enabled"];
+
+fn parse_csv_rules(content: &str) -> Result<ParsedRows, String> {
+    let mut records = parse_csv(content.trim_start_matches('\u{feff}'))?.into_iter().peekable();
+
+    // Columns are found by name when there is a header row, otherwise taken in order
+    let mut columns: Vec<Option<usize>> = (0..CSV_COLUMNS.len()).map(Some).collect();
+    if let Some((_, header)) = records.peek() {
+        let has_column = |name: &str| header.iter().any(|f| f.trim().eq_ignore_ascii_case(name));
+        if has_column("spoken") && has_column("replacement") {
+            columns = CSV_COLUMNS
+                .iter()
+                .map(|name| header.iter().position(|f| f.trim().eq_ignore_ascii_case(name)))
+                .collect();
+            records.next();
+        }
+    }
+
+    Ok(records
+        .filter(|(_, fields)| fields.iter().any(|f| !f.trim().is_empty()))
+        .map(|(row, fields)| {
+            let field = |column: usize| {
+                columns[column]
+                    .and_then(|i| fields.get(i))
+                    .map(|f| f.as_str())
+                    .filter(|f| !f.trim().is_empty())
+            };
+            (row, csv_rule(field))
+        })
+        .collect())
+}
+
+fn csv_rule<'a>(field: impl Fn(usize) -> Option<&'a str>) -> Result<KeywordRule, String> {
+    let replacement = field(1).ok_or("Missing replacement")?;
+    let match_mode = match field(2).map(|m| m.trim().to_lowercase()).as_deref() {
+        None | Some("whole_word") => MatchMode::WholeWord,
+        Some("substring") => MatchMode::Substring,
+        Some("regex") => MatchMode::Regex,
+        Some(other) => return Err(format!("Unknown match mode \"{}\"", other)),
+    };
+    let flag = |column: usize, default: bool| match field(column).map(|f| f.trim().to_lowercase()).as_deref() {
+        None => Ok(default),
+        Some("true" | "yes" | "1") => Ok(true),
+        Some("false" | "no" | "0") => Ok(false),
+        Some(other) => Err(format!("Expected true or false for {}, got \"{}\"", CSV_COLUMNS[column], other)),
+    };
+
+    Ok(KeywordRule {
+        spoken: field(0).unwrap_or_default().to_string(),
+        replacement: replacement.to_string(),
+        match_mode,
+        preserve_case: flag(3, false)?,
+        enabled: flag(4, true)?,
+    })
+}
+
+// Split CSV into records of fields, with the line number each record starts on.
+// Handles quoted fields containing commas, doubled quotes and line breaks.
+fn parse_csv(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                fields.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut fields)));
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!("Unclosed quote in the record starting on line {}", record_line));
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+    Ok(records)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.trim() != value {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn match_mode_name(mode: MatchMode) -> &'static str {
+    match mode {
+        MatchMode::WholeWord => "whole_word",
+        MatchMode::Substring => "substring",
+        MatchMode::Regex => "regex",
+    }
+}
+
+// Add a rule, replacing any rule for the same match mode and spoken text
+pub fn upsert(rules: &mut Vec<KeywordRule>, rule: KeywordRule) {
+    match rules.iter_mut().find(|r| r.is_for(rule.match_mode, &rule.spoken)) {
+        Some(existing) => *existing = rule,
+        None => rules.push(rule),
+    }
+}
+
+// Apply every enabled rule in a single pass, so a replacement is never matched again
+// by another rule. Where several literal rules match at the same spot the longest
+// spoken text wins, e.g. "open ai" beats "ai"; regex rules come after them in list order.
+pub fn apply(text: &str, rules: &[KeywordRule]) -> String {
+    match Matcher::new(rules) {
+        Some(matcher) => matcher.replace_all(text),
+        None => text.to_string(),
+    }
+}
+
+// Compiled size allowed for all rules together; the regex default of 10 MB runs out
+// at around 15,000 whole-word rules
+const COMBINED_SIZE_LIMIT: usize = 64 << 20;
+
+// Literal rules for one spoken text; whole-word and substring rules may both exist
+#[derive(Default)]
+struct LiteralRules<'a> {
+    whole_word: Option<&'a KeywordRule>,
+    substring: Option<&'a KeywordRule>,
+}
+
+struct PatternRule<'a> {
+    rule: &'a KeywordRule,
+    // Index of the group wrapping this rule in the combined regex
+    group: usize,
+    // The rule on its own, to read its capture groups for $1 substitution
+    regex: Regex,
+}
+
+// All rules combined into one regex. Literal rules share a single group and are
+// looked up by the matched text, since a capture group per rule makes matching
+// memory grow with the square of the rule count; only regex rules get groups.
+struct Matcher<'a> {
+    regex: Regex,
+    // Group 1 when there are literal rules; keyed by lowercased spoken text
+    literals: HashMap<String, LiteralRules<'a>>,
+    patterns: Vec<PatternRule<'a>>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(rules: &'a [KeywordRule]) -> Option<Self> {
+        let usable = |r: &&KeywordRule| r.enabled && r.validate().is_ok();
+
+        let mut literals: HashMap<String, LiteralRules<'a>> = HashMap::new();
+        for rule in rules.iter().filter(usable).filter(|r| r.match_mode != MatchMode::Regex) {
+            let entry = literals.entry(rule.spoken.trim().to_lowercase()).or_default();
+            let slot = match rule.match_mode {
+                MatchMode::WholeWord => &mut entry.whole_word,
+                _ => &mut entry.substring,
+            };
+            // The first rule in the list wins if the same one is there twice
+            slot.get_or_insert(rule);
+        }
+
+        // Alternation takes the first alternative that matches, so longer keywords go
+        // first, and a whole-word rule before a substring rule for the same text. Ties
+        // are broken alphabetically to keep the order stable.
+        let mut keys: Vec<&String> = literals.keys().collect();
+        keys.sort_by(|a, b| b.chars().count().cmp(&a.chars().count()).then_with(|| a.cmp(b)));
+        let literal_patterns: Vec<String> = keys
+            .iter()
+            .flat_map(|key| {
+                let entry = &literals[*key];
+                entry.whole_word.into_iter().chain(entry.substring)
+            })
+            .map(|rule| format!("(?:{})", rule_pattern(rule)))
+            .collect();
+
+        let mut alternatives = Vec::new();
+        let mut group = 1;
+        if !literal_patterns.is_empty() {
+            alternatives.push(format!("({})", literal_patterns.join("|")));
+            group += 1;
+        }
+
+        let mut patterns = Vec::new();
+        for rule in rules.iter().filter(usable).filter(|r| r.match_mode == MatchMode::Regex) {
+            let regex = build_regex(&rule.spoken).ok()?;
+            let inner_groups = regex.captures_len() - 1;
+            patterns.push(PatternRule { rule, group, regex });
+            alternatives.push(format!("({})", rule.spoken));
+            group += 1 + inner_groups;
+        }
+        if alternatives.is_empty() {
+            return None;
+        }
+
+        let combined = RegexBuilder::new(&alternatives.join("|"))
+            .case_insensitive(true)
+            .size_limit(COMBINED_SIZE_LIMIT)
+            .build();
+        let regex = match combined {
+            Ok(regex) => regex,
+            Err(e) => {
+                eprintln!("Failed to build keyword pattern: {}", e);
+                return None;
+            }
+        };
+
+        Some(Self {
+            regex,
+            literals,
+            patterns,
+        })
+    }
+
+    fn replace_all(&self, text: &str) -> String {
+        self.regex
+            .replace_all(text, |caps: &Captures| {
+                let Some(matched) = caps.get(0).filter(|m| !m.is_empty()) else {
+                    return String::new();
+                };
+                let replacement = if !self.literals.is_empty() && caps.get(1).is_some() {
+                    self.literal_replacement(text, matched)
+                } else {
+                    self.patterns
+                        .iter()
+                        .find(|p| caps.get(p.group).is_some())
+                        .map(|p| p.replacement(text, matched))
+                };
+                replacement.unwrap_or_else(|| matched.as_str().to_string())
+            })
+            .into_owned()
+    }
+
+    fn literal_replacement(&self, text: &str, matched: regex::Match) -> Option<String> {
+        let entry = self.literals.get(&matched.as_str().to_lowercase())?;
+        // The whole-word rule matched if the text around it allows, otherwise the substring one did
+        let rule = entry
+            .whole_word
+            .filter(|_| is_whole_word(text, matched.start(), matched.end()))
+            .or(entry.substring)?;
+        Some(finish_replacement(rule, rule.replacement.clone(), matched.as_str()))
+    }
+}
+
+impl PatternRule<'_> {
+    fn replacement(&self, text: &str, matched: regex::Match) -> String {
+        let mut replacement = self.rule.replacement.clone();
+        // Matching the rule again from the same spot gives its own numbered groups
+        if let Some(caps) = self.regex.captures_at(text, matched.start()) {
+            replacement.clear();
+            caps.expand(&self.rule.replacement, &mut replacement);
+        }
+        finish_replacement(self.rule, replacement, matched.as_str())
+    }
+}
+
+fn finish_replacement(rule: &KeywordRule, replacement: String, matched: &str) -> String {
+    if rule.preserve_case {
+        match_case(&replacement, matched)
+    } else {
+        replacement
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Whether text[start..end] has the boundaries a whole-word rule puts on its word-character edges
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let matched = &text[start..end];
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    let edge_ok = |edge: Option<char>, inner: Option<char>| {
+        !inner.is_some_and(is_word_char) || !edge.is_some_and(is_word_char)
+    };
+    edge_ok(before, matched.chars().next()) && edge_ok(after, matched.chars().next_back())
+}
+
+fn build_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+// Pattern for a literal rule
+fn rule_pattern(rule: &KeywordRule) -> String {
+    let spoken = rule.spoken.trim();
+    let escaped = regex::escape(spoken);
+    if rule.match_mode == MatchMode::Substring {
+        return escaped;
+    }
+
+    // \b only works next to a word character, so keywords such as "c++" or ".net"
+    // get a boundary on their word-character edges only
+    let start = if spoken.starts_with(is_word_char) { r"\b" } else { "" };
+    let end = if spoken.ends_with(is_word_char) { r"\b" } else { "" };
+    format!("{}{}{}", start, escaped, end)
+}
+
+// Give the replacement the capitalization of the matched text: all caps stays all
+// caps and a capitalized word stays capitalized. Otherwise it is left as written.
+fn match_case(replacement: &str, matched: &str) -> String {
+    let letters: Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        return replacement.to_uppercase();
+    }
+    if !letters.first().is_some_and(|c| c.is_uppercase()) {
+        return replacement.to_string();
+    }
+
+    let mut chars = replacement.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(spoken: &str, replacement: &str, match_mode: MatchMode) -> KeywordRule {
+        KeywordRule {
+            spoken: spoken.to_string(),
+            replacement: replacement.to_string(),
+            match_mode,
+            preserve_case: false,
+            enabled: true,
+        }
+    }
+
+    fn word(spoken: &str, replacement: &str) -> KeywordRule {
+        rule(spoken, replacement, MatchMode::WholeWord)
+    }
+
+    #[test]
+    fn whole_word_leaves_longer_words_alone() {
+        let rules = [word("ai", "AI")];
+        assert_eq!(apply("I paid to maintain the ai model", &rules), "I paid to maintain the AI model");
+        assert_eq!(apply("Ai, ai. (ai)", &rules), "AI, AI. (AI)");
+    }
+
+    #[test]
+    fn longest_keyword_wins() {
+        let rules = [word("ai", "AI"), word("open ai", "OpenAI")];
+        assert_eq!(apply("open ai ships ai tools", &rules), "OpenAI ships AI tools");
+    }
+
+    #[test]
+    fn replacements_are_not_matched_again() {
+        let rules = [word("gpt", "chat gpt"), word("chat", "Chat")];
+        assert_eq!(apply("gpt and chat", &rules), "chat gpt and Chat");
+    }
+
+    #[test]
+    fn substring_matches_inside_words() {
+        let rules = [rule("colour", "color", MatchMode::Substring)];
+        assert_eq!(apply("colourful colours", &rules), "colorful colors");
+    }
+
+    #[test]
+    fn keywords_with_non_word_edges() {
+        let rules = [word("c++", "C++"), word(".net", ".NET")];
+        assert_eq!(apply("I write c++, and .net code", &rules), "I write C++, and .NET code");
+        // Word-character edges still need a boundary
+        assert_eq!(apply("abc++ and the .network", &rules), "abc++ and the .network");
+    }
+
+    #[test]
+    fn rules_are_keyed_on_match_mode_and_spoken_text() {
+        let mut rules = vec![word("ai", "AI")];
+        upsert(&mut rules, rule("ai", "Ai", MatchMode::Substring));
+        upsert(&mut rules, word("AI", "A.I."));
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].replacement, "A.I.");
+        assert!(rules[1].is_for(MatchMode::Substring, "ai"));
+        assert!(!rules[1].is_for(MatchMode::WholeWord, "ai"));
+
+        let mut pattern = vec![rule(r"\d+", "N", MatchMode::Regex)];
+        upsert(&mut pattern, rule(r"\D+", "X", MatchMode::Regex));
+        assert_eq!(pattern.len(), 2);
+    }
+
+    #[test]
+    fn regex_replacement_uses_groups() {
+        let rules = [rule(r"ticket (\d+)", "JIRA-$1", MatchMode::Regex)];
+        assert_eq!(apply("see ticket 42 and Ticket 7", &rules), "see JIRA-42 and JIRA-7");
+    }
+
+    #[test]
+    fn preserve_case_follows_matched_text() {
+        let rules = [KeywordRule {
+            preserve_case: true,
+            ..word("colour", "color")
+        }];
+        assert_eq!(apply("colour Colour COLOUR", &rules), "color Color COLOR");
+
+        // Without it the replacement is used as written
+        let rules = [word("colour", "color")];
+        assert_eq!(apply("Colour COLOUR", &rules), "color color");
+    }
+
+    #[test]
+    fn disabled_rules_are_skipped() {
+        let rules = [
+            KeywordRule {
+                enabled: false,
+                ..word("ai", "AI")
+            },
+            KeywordRule {
+                enabled: false,
+                ..rule(r"ticket (\d+)", "JIRA-$1", MatchMode::Regex)
+            },
+        ];
+        assert_eq!(apply("ai ticket 1", &rules), "ai ticket 1");
+    }
+
+    #[test]
+    fn validate_rejects_unusable_patterns() {
+        assert!(rule(r"ticket (\d+)", "JIRA-$1", MatchMode::Regex).validate().is_ok());
+        assert!(rule("ticket (", "x", MatchMode::Regex).validate().is_err());
+        assert!(rule(r"(?P<id>\d+)", "x", MatchMode::Regex).validate().is_err());
+        assert!(rule(r"\d*", "x", MatchMode::Regex).validate().is_err());
+        assert!(rule("  ", "x", MatchMode::WholeWord).validate().is_err());
+        // Invalid rules are ignored when applying instead of breaking every rule
+        let rules = [rule("ticket (", "x", MatchMode::Regex), word("ai", "AI")];
+        assert_eq!(apply("ai ticket (", &rules), "AI ticket (");
+    }
+
+    #[test]
+    fn legacy_map_becomes_sorted_whole_word_rules() {
+        let stored = serde_json::json!({"open ai": "OpenAI", "gpt": "GPT"});
+        let rules = from_stored(&stored);
+        assert_eq!(rules.len(), 2);
+        assert_eq!((rules[0].spoken.as_str(), rules[0].replacement.as_str()), ("gpt", "GPT"));
+        assert_eq!((rules[1].spoken.as_str(), rules[1].replacement.as_str()), ("open ai", "OpenAI"));
+        assert!(rules.iter().all(|r| r.match_mode == MatchMode::WholeWord && r.enabled && !r.preserve_case));
+    }
+
+    #[test]
+    fn from_stored_skips_only_bad_entries() {
+        let stored = serde_json::json!([
+            {"spoken": "ai", "replacement": "AI"},
+            {"spoken": "gpt"},
+            {"spoken": "colour", "replacement": "color", "match_mode": "sometimes"},
+            {"spoken": "c++", "replacement": "C++", "match_mode": "substring", "enabled": false},
+        ]);
+        let rules = from_stored(&stored);
+        let spoken: Vec<&str> = rules.iter().map(|r| r.spoken.as_str()).collect();
+        assert_eq!(spoken, ["ai", "c++"]);
+        assert!(!rules[1].enabled);
+
+        let stored = serde_json::json!({"ai": "AI", "gpt": 4});
+        assert_eq!(from_stored(&stored).len(), 1);
+        assert!(from_stored(&serde_json::json!("ai")).is_empty());
+    }
+
+    fn import_csv(rules: &mut Vec<KeywordRule>, content: &str, mode: ImportMode) -> ImportReport {
+        import(rules, content, Some(KeywordFormat::Csv), mode, false).unwrap()
+    }
+
+    fn spoken(rules: &[KeywordRule]) -> Vec<&str> {
+        rules.iter().map(|r| r.spoken.as_str()).collect()
+    }
+
+    fn rows(issues: &[ImportIssue]) -> Vec<usize> {
+        issues.iter().map(|i| i.row).collect()
+    }
+
+    #[test]
+    fn csv_quoted_fields() {
+        let mut rules = Vec::new();
+        let content = "\"hello, world\",\"Hello, World\"\n\"say \"\"hi\"\"\",\"\"\"Hi\"\"\"\n\"two\nlines\",Two lines\nlast,Last\n";
+        let report = import_csv(&mut rules, content, ImportMode::Merge);
+
+        assert_eq!(report.added, 4);
+        assert_eq!(spoken(&rules), ["hello, world", "say \"hi\"", "two\nlines", "last"]);
+        assert_eq!(rules[0].replacement, "Hello, World");
+        assert_eq!(rules[1].replacement, "\"Hi\"");
+        assert!(report.invalid.is_empty());
+    }
+
+    #[test]
+    fn csv_line_endings_and_bom() {
+        let lf = "spoken,replacement\nai,AI\n\"open\nai\",OpenAI\nbad\n";
+        let crlf = lf.replace('\n', "\r\n");
+        let bom = format!("\u{feff}{}", crlf);
+
+        for (content, newline) in [(lf.to_string(), "\n"), (crlf, "\r\n"), (bom, "\r\n")] {
+            let mut rules = Vec::new();
+            let report = import_csv(&mut rules, &content, ImportMode::Merge);
+            // Line breaks inside quotes are kept as written
+            assert_eq!(spoken(&rules), ["ai".to_string(), format!("open{}ai", newline)]);
+            assert_eq!(rules[1].replacement, "OpenAI");
+            // Rows are numbered by line, counting the header and the quoted line break
+            assert_eq!(rows(&report.invalid), [5]);
+        }
+    }
+
+    #[test]
+    fn csv_with_and_without_header() {
+        let mut rules = Vec::new();
+        import_csv(&mut rules, "ai,AI,substring,yes,0\n", ImportMode::Merge);
+        assert_eq!(rules[0].match_mode, MatchMode::Substring);
+        assert!(rules[0].preserve_case);
+        assert!(!rules[0].enabled);
+
+        // A header finds columns by name, in any order, and missing ones get defaults
+        let mut rules = Vec::new();
+        import_csv(&mut rules, "Enabled,Spoken,Replacement\nfalse,gpt,GPT\n", ImportMode::Merge);
+        assert_eq!(spoken(&rules), ["gpt"]);
+        assert_eq!(rules[0].replacement, "GPT");
+        assert_eq!(rules[0].match_mode, MatchMode::WholeWord);
+        assert!(!rules[0].enabled);
+    }
+
+    #[test]
+    fn csv_unclosed_quote_fails_the_import() {
+        let mut rules = vec![word("ai", "AI")];
+        assert!(import(&mut rules, "\"ai,AI\n", Some(KeywordFormat::Csv), ImportMode::Replace, true).is_err());
+        assert_eq!(spoken(&rules), ["ai"]);
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let original = vec![
+            word("hello, world", "Hello, World"),
+            KeywordRule {
+                preserve_case: true,
+                enabled: false,
+                ..rule("say \"hi\"", " padded ", MatchMode::Substring)
+            },
+            rule(r"ticket (\d+)", "JIRA-$1", MatchMode::Regex),
+            word("two\nlines", "Two\r\nlines"),
+        ];
+
+        for format in [KeywordFormat::Csv, KeywordFormat::Json] {
+            let content = export(&original, format).unwrap();
+            let mut rules = Vec::new();
+            let report = import(&mut rules, &content, None, ImportMode::Replace, false).unwrap();
+            assert!(report.applied);
+            assert_eq!(report.added, original.len(), "{:?}", format);
+            assert!(rules.iter().zip(&original).all(|(a, b)| same_rule(a, b)), "{:?}", format);
+        }
+    }
+
+    fn existing_rules() -> Vec<KeywordRule> {
+        vec![word("ai", "AI"), word("gpt", "GPT"), word("colour", "color")]
+    }
+
+    const UPDATE_CSV: &str = "ai,AI\ngpt,ChatGPT\nnew,New\n";
+
+    #[test]
+    fn merge_keeps_rules_missing_from_the_file() {
+        let mut rules = existing_rules();
+        let report = import_csv(&mut rules, UPDATE_CSV, ImportMode::Merge);
+
+        assert!(report.applied);
+        assert_eq!((report.added, report.updated, report.unchanged, report.removed), (1, 1, 1, 0));
+        assert_eq!(spoken(&rules), ["ai", "gpt", "colour", "new"]);
+        assert_eq!(rules[1].replacement, "ChatGPT");
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!((report.conflicts[0].existing.as_str(), report.conflicts[0].imported.as_str()), ("GPT", "ChatGPT"));
+    }
+
+    #[test]
+    fn replace_reports_conflicts_and_removals() {
+        let mut rules = existing_rules();
+        let report = import_csv(&mut rules, UPDATE_CSV, ImportMode::Replace);
+
+        assert!(report.applied);
+        assert_eq!((report.added, report.updated, report.unchanged, report.removed), (1, 1, 1, 1));
+        assert_eq!(spoken(&rules), ["ai", "gpt", "new"]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].spoken, "gpt");
+    }
+
+    #[test]
+    fn replace_needs_force_to_skip_rows_or_empty_the_list() {
+        for content in ["", "spoken,replacement\n", "ai,AI\nbad\n"] {
+            let mut rules = existing_rules();
+            let report = import_csv(&mut rules, content, ImportMode::Replace);
+            assert!(!report.applied, "{:?}", content);
+            assert_eq!(rules.len(), 3, "{:?}", content);
+
+            let report = import(&mut rules, content, Some(KeywordFormat::Csv), ImportMode::Replace, true).unwrap();
+            assert!(report.applied, "{:?}", content);
+        }
+
+        // Merging never removes anything, so it doesn't need confirming
+        let mut rules = existing_rules();
+        assert!(import_csv(&mut rules, "new,New\nbad\n", ImportMode::Merge).applied);
+        assert_eq!(rules.len(), 4);
+    }
+
+    #[test]
+    fn import_reports_duplicates_and_invalid_rows() {
+        let content = "spoken,replacement,match_mode,preserve_case\n\
+                       ai,AI\n\
+                       AI,A.I.\n\
+                       ai,Ai,substring\n\
+                       colour,color,sometimes\n\
+                       gpt,GPT,whole_word,maybe\n\
+                       ,nothing\n\
+                       lonely\n\
+                       (,x,regex\n";
+        let mut rules = Vec::new();
+        let report = import_csv(&mut rules, content, ImportMode::Merge);
+
+        // Whole-word and substring rules for the same text are different rules
+        assert_eq!(spoken(&rules), ["ai", "ai"]);
+        assert_eq!(rows(&report.duplicates), [3]);
+        assert_eq!(rows(&report.invalid), [5, 6, 7, 8, 9]);
+        assert!(report.invalid[0].message.contains("sometimes"));
+        assert!(report.invalid[1].message.contains("preserve_case"));
+    }
+
+    #[test]
+    fn json_import_accepts_rule_lists_and_legacy_maps() {
+        let mut rules = Vec::new();
+        let content = r#"[{"spoken": "ai", "replacement": "AI"}, {"spoken": "gpt"}, 3]"#;
+        let report = import(&mut rules, content, None, ImportMode::Merge, false).unwrap();
+        assert_eq!(spoken(&rules), ["ai"]);
+        assert_eq!(rows(&report.invalid), [2, 3]);
+
+        let mut rules = Vec::new();
+        import(&mut rules, r#"{"gpt": "GPT", "ai": "AI"}"#, None, ImportMode::Merge, false).unwrap();
+        assert_eq!(spoken(&rules), ["ai", "gpt"]);
+    }
+
+    #[test]
+    fn json_import_rejects_other_objects() {
+        let mut rules = existing_rules();
+        for content in [r#"{"rules": [{"spoken": "ai", "replacement": "AI"}]}"#, r#"{"ai": 1}"#, "42"] {
+            let result = import(&mut rules, content, Some(KeywordFormat::Json), ImportMode::Replace, true);
+            assert!(result.is_err(), "{}", content);
+        }
+        assert_eq!(rules.len(), 3);
+    }
+
+    #[test]
+    fn thousands_of_rules() {
+        let mut rules: Vec<KeywordRule> = (0..5000).map(|i| word(&format!("term{}", i), &format!("Term{}", i))).collect();
+        rules.extend((0..1000).map(|i| rule(&format!("part{}x", i), &format!("P{}", i), MatchMode::Substring)));
+        rules.push(rule(r"ticket (\d+)", "JIRA-$1", MatchMode::Regex));
+
+        let text = "term42 and term4999 in apart7xy, term42x and ticket 9";
+        assert_eq!(apply(text, &rules), "Term42 and Term4999 in aP7y, term42x and JIRA-9");
+    }
+
+    #[test]
+    fn whole_word_and_substring_rules_for_the_same_text() {
+        let rules = [word("ai", "AI"), rule("ai", "[ai]", MatchMode::Substring)];
+        assert_eq!(apply("ai paid", &rules), "AI p[ai]d");
+    }
+
+    #[test]
+    fn output_does_not_depend_on_rule_order() {
+        let mut rules = vec![
+            word("ai", "AI"),
+            word("open ai", "OpenAI"),
+            word("open", "Open"),
+            rule("gpt", "GPT", MatchMode::Substring),
+            word("c++", "C++"),
+        ];
+        let text = "open ai and open source chatgpt in c++ with ai";
+        let expected = "OpenAI and Open source chatGPT in C++ with AI";
+        assert_eq!(apply(text, &rules), expected);
+        rules.reverse();
+        assert_eq!(apply(text, &rules), expected);
+        rules.rotate_left(2);
+        assert_eq!(apply(text, &rules), expected);
+    }
+}
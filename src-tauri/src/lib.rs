@@ -6,10 +6,31 @@ use std::sync::{Arc, Mutex};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::TrayIconBuilder,
-    AppHandle, Manager, State, PhysicalPosition,
+    AppHandle, Emitter, Manager, State, PhysicalPosition,
 };
 use tauri_plugin_store::StoreExt;
 
+mod api;
+mod audio;
+mod dictation;
+mod formatting;
+mod history;
+mod injection;
+mod keywords;
+#[cfg(test)]
+mod mock_server;
+mod recordings;
+mod transcription;
+
+use api::{ApiError, HttpClient};
+use dictation::{HotkeyError, HotkeyMode};
+use formatting::FormattingBackend;
+use history::{HistoryEntry, HistoryState, NewHistoryEntry};
+use injection::{InjectOptions, InjectionError, InjectionMode};
+use keywords::{ImportMode, ImportReport, KeywordFormat, KeywordRule, MatchMode};
+use recordings::{RecordingCache, RecordingInfo};
+use transcription::TranscriptionBackend;
+
 // State for managing audio recording
 pub struct AudioState {
     is_recording: Arc<Mutex<bool>>,
@@ -27,14 +48,132 @@ impl Default for AudioState {
     }
 }
 
+// Connection settings for an OpenAI-compatible API endpoint
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EndpointConfig {
+    pub base_url: String,
+    pub model: String,
+    // Extra headers sent with every request (e.g. Azure's "api-key")
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // Bearer token for this endpoint; the global OpenAI key is only sent to OpenAI
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+impl EndpointConfig {
+    fn openai(model: &str) -> Self {
+        Self {
+            base_url: OPENAI_BASE_URL.to_string(),
+            model: model.to_string(),
+            headers: HashMap::new(),
+            api_key: None,
+        }
+    }
+
+    fn ollama(model: &str) -> Self {
+        Self {
+            base_url: OLLAMA_BASE_URL.to_string(),
+            model: model.to_string(),
+            headers: HashMap::new(),
+            api_key: None,
+        }
+    }
+
+    // Join a path like "/chat/completions" onto the base URL
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    pub fn is_openai(&self) -> bool {
+        self.base_url.trim_end_matches('/') == OPENAI_BASE_URL
+    }
+
+    // Only api.openai.com is guaranteed to reject unauthenticated requests;
+    // self-hosted servers and proxies often don't need a key at all
+    pub fn requires_api_key(&self) -> bool {
+        self.is_openai()
+    }
+
+    // Key to send as bearer auth. The user's OpenAI key is only ever sent to
+    // api.openai.com, never to proxies or self-hosted servers.
+    pub fn resolve_api_key(&self, openai_api_key: Option<&str>) -> Option<String> {
+        self.api_key
+            .as_deref()
+            .filter(|key| !key.is_empty())
+            .or_else(|| openai_api_key.filter(|_| self.requires_api_key()))
+            .map(|key| key.to_string())
+    }
+
+    // Add bearer auth (if a key is set) and the configured extra headers
+    pub fn apply_headers(&self, mut request: reqwest::RequestBuilder, api_key: Option<&str>) -> reqwest::RequestBuilder {
+        if let Some(key) = api_key {
+            request = request.header("Authorization", format!("Bearer {}", key));
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+}
+
+// When transcripts go through the LLM formatter
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormattingMode {
+    Off,
+    #[default]
+    On,
+    // Only format utterances with at least `auto_format_min_words` words
+    Auto,
+}
+
 // App settings state
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Settings {
     pub openai_api_key: Option<String>,
     pub custom_prompt: String,
-    pub keywords: HashMap<String, String>,
+    pub keywords: Vec<KeywordRule>,
     pub hotkey: String,
+    pub hotkey_mode: HotkeyMode,
+    // Optional shortcut that cancels a transcription in progress
+    pub cancel_hotkey: Option<String>,
     pub auto_paste: bool,
+    pub injection_mode: InjectionMode,
+    // Pause between characters when typing instead of pasting
+    pub typing_delay_ms: u64,
+    // Restore the previous clipboard contents after auto-pasting
+    pub preserve_clipboard: bool,
+    pub clipboard_restore_delay_ms: u64,
+    pub input_device: Option<String>,
+    // Channel to keep from multi-channel devices; None averages all channels
+    pub input_channel: Option<u16>,
+    // Resample recordings to 16 kHz before upload
+    pub resample_audio: bool,
+    // Trim leading/trailing silence and skip the API when nothing was said
+    pub trim_silence: bool,
+    pub transcription_backend: TranscriptionBackend,
+    // GGML model file used by the local backend
+    pub local_model_path: Option<String>,
+    pub transcription_endpoint: EndpointConfig,
+    pub formatting_endpoint: EndpointConfig,
+    pub formatting_temperature: f32,
+    // Cap on formatter output length; None leaves it to the server
+    pub formatting_max_tokens: Option<u32>,
+    pub formatting_mode: FormattingMode,
+    pub auto_format_min_words: usize,
+    pub formatting_backend: FormattingBackend,
+    pub ollama_endpoint: EndpointConfig,
+    // How many recent recordings to keep on disk for re-transcription; 0 disables
+    pub recording_cache_limit: usize,
+    pub connect_timeout_secs: u64,
+    // Limit for a whole request, including uploading audio and waiting for the reply
+    pub request_timeout_secs: u64,
+    // HTTP(S) proxy for API requests, e.g. "http://proxy.local:8080"
+    pub proxy_url: Option<String>,
 }
 
 // Embedded API key from .env at build time (if available)
@@ -45,9 +184,43 @@ impl Default for Settings {
         Self {
             openai_api_key: EMBEDDED_API_KEY.map(|s| s.to_string()),
             custom_prompt: DEFAULT_PROMPT.to_string(),
-            keywords: HashMap::new(),
+            keywords: Vec::new(),
             hotkey: "Control+Space".to_string(),
+            hotkey_mode: HotkeyMode::default(),
+            cancel_hotkey: None,
             auto_paste: true,
+            injection_mode: InjectionMode::default(),
+            typing_delay_ms: 0,
+            preserve_clipboard: true,
+            clipboard_restore_delay_ms: 500,
+            input_device: None,
+            input_channel: None,
+            resample_audio: true,
+            trim_silence: true,
+            transcription_backend: TranscriptionBackend::default(),
+            local_model_path: None,
+            transcription_endpoint: EndpointConfig::openai("whisper-1"),
+            formatting_endpoint: EndpointConfig::openai("gpt-5.2"),
+            formatting_temperature: 0.3,
+            formatting_max_tokens: None,
+            formatting_mode: FormattingMode::default(),
+            auto_format_min_words: 8,
+            formatting_backend: FormattingBackend::default(),
+            ollama_endpoint: EndpointConfig::ollama("llama3.2"),
+            recording_cache_limit: 20,
+            connect_timeout_secs: 10,
+            request_timeout_secs: 120,
+            proxy_url: None,
+        }
+    }
+}
+
+impl Settings {
+    // Endpoint of the formatter currently in use
+    fn formatting_endpoint_mut(&mut self) -> &mut EndpointConfig {
+        match self.formatting_backend {
+            FormattingBackend::OpenAi => &mut self.formatting_endpoint,
+            FormattingBackend::Ollama => &mut self.ollama_endpoint,
         }
     }
 }
@@ -135,38 +308,119 @@ impl Default for AppState {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct WhisperResponse {
-    text: String,
+// Lets `cancel_transcription` stop the transcription that's in progress
+#[derive(Default)]
+pub struct TranscriptionState {
+    cancel: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
+impl TranscriptionState {
+    // Register a new run; the receiver fires if it gets cancelled. A run ends when it
+    // drops its receiver, and only one may be in progress so each can be cancelled.
+    fn begin(&self) -> Result<tokio::sync::oneshot::Receiver<()>, ApiError> {
+        let mut cancel = self.cancel.lock().map_err(|e| e.to_string())?;
+        if cancel.as_ref().is_some_and(|tx| !tx.is_closed()) {
+            return Err(ApiError::Busy {
+                message: "A transcription is already in progress".to_string(),
+            });
+        }
+
+        let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+        *cancel = Some(cancel_tx);
+        Ok(cancel_rx)
+    }
+
+    // Returns false if nothing was running
+    fn cancel(&self) -> Result<bool, String> {
+        let cancel_tx = self.cancel.lock().map_err(|e| e.to_string())?.take();
+        Ok(cancel_tx.is_some_and(|tx| tx.send(()).is_ok()))
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<ChatMessage>,
-    temperature: f32,
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatChoice {
-    message: ChatMessage,
+// Which configured endpoint `list_models` should query
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModelEndpoint {
+    Transcription,
+    Formatting,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatResponse {
-    choices: Vec<ChatChoice>,
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionOutcome {
+    Transcribed,
+    // Recording contained no speech, so nothing was sent to the API
+    NoSpeech,
+    // Stopped by `cancel_transcription`; nothing should be injected
+    Cancelled,
+}
+
+// Which route the transcript took after speech-to-text
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FormattingPath {
+    // Sent through the LLM formatter
+    Formatted,
+    // Formatting mode is off
+    SkippedDisabled,
+    // Auto mode and the utterance was below the word threshold
+    SkippedShort,
+    // Formatter isn't usable (no API key for api.openai.com)
+    SkippedNoApiKey,
+    // Formatter request failed (e.g. offline), so the unformatted text was used
+    FormatterFailed,
+    // Nothing was transcribed
+    None,
 }
 
 #[derive(Serialize, Clone)]
 pub struct TranscriptionResult {
+    outcome: TranscriptionOutcome,
     raw_text: String,
     formatted_text: String,
+    formatting: FormattingPath,
+    // Formatter output looked like a reply rather than a reformat, so the
+    // keyword-processed text was used instead
+    guardrail_triggered: bool,
+    // Why formatting failed when `formatting` is FormatterFailed
+    formatting_error: Option<ApiError>,
+    // Row id in the history database; None if nothing was saved
+    history_id: Option<i64>,
+    // Cached audio, usable with `retranscribe`
+    recording_id: Option<String>,
+}
+
+// Input device info returned to the frontend
+#[derive(Serialize, Clone)]
+pub struct InputDeviceInfo {
+    name: String,
+    is_default: bool,
+    configs: Vec<InputConfigInfo>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct InputConfigInfo {
+    channels: u16,
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    sample_format: String,
+}
+
+// Emitted when the saved input device can't be found and the default is used instead
+#[derive(Serialize, Clone)]
+pub struct InputDeviceFallback {
+    requested: String,
+    fallback: Option<String>,
 }
 
 // Helper to save settings to persistent store
@@ -176,7 +430,31 @@ fn persist_settings(app: &AppHandle, settings: &Settings) -> Result<(), String>
     store.set("custom_prompt", settings.custom_prompt.clone());
     store.set("keywords", serde_json::to_value(&settings.keywords).unwrap_or_default());
     store.set("hotkey", settings.hotkey.clone());
+    store.set("hotkey_mode", serde_json::to_value(settings.hotkey_mode).unwrap_or_default());
+    store.set("cancel_hotkey", settings.cancel_hotkey.clone().unwrap_or_default());
     store.set("auto_paste", settings.auto_paste);
+    store.set("injection_mode", serde_json::to_value(settings.injection_mode).unwrap_or_default());
+    store.set("typing_delay_ms", settings.typing_delay_ms);
+    store.set("preserve_clipboard", settings.preserve_clipboard);
+    store.set("clipboard_restore_delay_ms", settings.clipboard_restore_delay_ms);
+    store.set("input_device", settings.input_device.clone().unwrap_or_default());
+    store.set("input_channel", serde_json::to_value(settings.input_channel).unwrap_or_default());
+    store.set("resample_audio", settings.resample_audio);
+    store.set("trim_silence", settings.trim_silence);
+    store.set("transcription_backend", serde_json::to_value(settings.transcription_backend).unwrap_or_default());
+    store.set("local_model_path", settings.local_model_path.clone().unwrap_or_default());
+    store.set("transcription_endpoint", serde_json::to_value(&settings.transcription_endpoint).unwrap_or_default());
+    store.set("formatting_endpoint", serde_json::to_value(&settings.formatting_endpoint).unwrap_or_default());
+    store.set("formatting_temperature", settings.formatting_temperature);
+    store.set("formatting_max_tokens", serde_json::to_value(settings.formatting_max_tokens).unwrap_or_default());
+    store.set("formatting_mode", serde_json::to_value(settings.formatting_mode).unwrap_or_default());
+    store.set("auto_format_min_words", settings.auto_format_min_words);
+    store.set("formatting_backend", serde_json::to_value(settings.formatting_backend).unwrap_or_default());
+    store.set("ollama_endpoint", serde_json::to_value(&settings.ollama_endpoint).unwrap_or_default());
+    store.set("recording_cache_limit", settings.recording_cache_limit);
+    store.set("connect_timeout_secs", settings.connect_timeout_secs);
+    store.set("request_timeout_secs", settings.request_timeout_secs);
+    store.set("proxy_url", settings.proxy_url.clone().unwrap_or_default());
     store.save().map_err(|e| e.to_string())?;
     Ok(())
 }
@@ -197,24 +475,150 @@ fn load_persisted_settings(app: &AppHandle) -> Settings {
         .and_then(|v| v.as_str().map(|s| s.to_string()))
         .unwrap_or_else(|| DEFAULT_PROMPT.to_string());
 
-    let keywords: HashMap<String, String> = store.get("keywords")
-        .and_then(|v| serde_json::from_value(v.clone()).ok())
+    // Converts the {"spoken": "replacement"} map older versions stored
+    let keywords: Vec<KeywordRule> = store.get("keywords")
+        .map(|v| keywords::from_stored(&v))
         .unwrap_or_default();
 
     let hotkey: String = store.get("hotkey")
         .and_then(|v| v.as_str().map(|s| s.to_string()))
         .unwrap_or_else(|| "Control+Space".to_string());
 
+    let hotkey_mode: HotkeyMode = store.get("hotkey_mode")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let cancel_hotkey: Option<String> = store.get("cancel_hotkey")
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty());
+
     let auto_paste: bool = store.get("auto_paste")
         .and_then(|v| v.as_bool())
         .unwrap_or(true);
 
+    let injection_mode: InjectionMode = store.get("injection_mode")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let typing_delay_ms: u64 = store.get("typing_delay_ms")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+
+    let preserve_clipboard: bool = store.get("preserve_clipboard")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    let clipboard_restore_delay_ms: u64 = store.get("clipboard_restore_delay_ms")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(500);
+
+    let input_device: Option<String> = store.get("input_device")
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty());
+
+    let input_channel: Option<u16> = store.get("input_channel")
+        .and_then(|v| v.as_u64())
+        .map(|c| c as u16);
+
+    let resample_audio: bool = store.get("resample_audio")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    let trim_silence: bool = store.get("trim_silence")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    let transcription_backend: TranscriptionBackend = store.get("transcription_backend")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let local_model_path: Option<String> = store.get("local_model_path")
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty());
+
+    let transcription_endpoint: EndpointConfig = store.get("transcription_endpoint")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_else(|| EndpointConfig::openai("whisper-1"));
+
+    let formatting_endpoint: EndpointConfig = store.get("formatting_endpoint")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_else(|| EndpointConfig::openai("gpt-5.2"));
+
+    let formatting_temperature: f32 = store.get("formatting_temperature")
+        .and_then(|v| v.as_f64())
+        .map(|t| t as f32)
+        .unwrap_or(0.3);
+
+    let formatting_max_tokens: Option<u32> = store.get("formatting_max_tokens")
+        .and_then(|v| v.as_u64())
+        .map(|t| t as u32);
+
+    let formatting_mode: FormattingMode = store.get("formatting_mode")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let auto_format_min_words: usize = store.get("auto_format_min_words")
+        .and_then(|v| v.as_u64())
+        .map(|w| w as usize)
+        .unwrap_or(8);
+
+    let formatting_backend: FormattingBackend = store.get("formatting_backend")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let ollama_endpoint: EndpointConfig = store.get("ollama_endpoint")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_else(|| EndpointConfig::ollama("llama3.2"));
+
+    let recording_cache_limit: usize = store.get("recording_cache_limit")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize)
+        .unwrap_or(20);
+
+    let connect_timeout_secs: u64 = store.get("connect_timeout_secs")
+        .and_then(|v| v.as_u64())
+        .filter(|&t| t > 0)
+        .unwrap_or(10);
+
+    let request_timeout_secs: u64 = store.get("request_timeout_secs")
+        .and_then(|v| v.as_u64())
+        .filter(|&t| t > 0)
+        .unwrap_or(120);
+
+    let proxy_url: Option<String> = store.get("proxy_url")
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty());
+
     Settings {
         openai_api_key: api_key,
         custom_prompt,
         keywords,
         hotkey,
+        hotkey_mode,
+        cancel_hotkey,
         auto_paste,
+        injection_mode,
+        typing_delay_ms,
+        preserve_clipboard,
+        clipboard_restore_delay_ms,
+        input_device,
+        input_channel,
+        resample_audio,
+        trim_silence,
+        transcription_backend,
+        local_model_path,
+        transcription_endpoint,
+        formatting_endpoint,
+        formatting_temperature,
+        formatting_max_tokens,
+        formatting_mode,
+        auto_format_min_words,
+        formatting_backend,
+        ollama_endpoint,
+        recording_cache_limit,
+        connect_timeout_secs,
+        request_timeout_secs,
+        proxy_url,
     }
 }
 
@@ -225,12 +629,38 @@ fn get_settings(app_state: State<AppState>) -> Result<Settings, String> {
     Ok(settings.clone())
 }
 
+// Replace every setting at once, with the same checks as the individual setters.
+// Nothing is changed unless all of them pass and the hotkeys could be registered.
 #[tauri::command]
-fn save_settings(app: AppHandle, new_settings: Settings, app_state: State<AppState>) -> Result<(), String> {
+fn save_settings(
+    app: AppHandle,
+    new_settings: Settings,
+    app_state: State<AppState>,
+    http: State<HttpClient>,
+) -> Result<(), HotkeyError> {
+    validate_endpoint(&new_settings.transcription_endpoint)?;
+    validate_endpoint(&new_settings.formatting_endpoint)?;
+    validate_endpoint(&new_settings.ollama_endpoint)?;
+    for rule in &new_settings.keywords {
+        rule.validate().map_err(|e| format!("Keyword \"{}\": {}", rule.spoken, e))?;
+    }
+    // Catches a bad proxy URL before anything is applied
+    api::build_client(&new_settings)?;
+
+    let current = app_state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let hotkeys_changed = current.hotkey != new_settings.hotkey
+        || current.hotkey_mode != new_settings.hotkey_mode
+        || current.cancel_hotkey != new_settings.cancel_hotkey;
+    if hotkeys_changed {
+        dictation::update_shortcuts(&app, &current, &new_settings)?;
+    }
+
+    http.configure(&new_settings)?;
     let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
     *settings = new_settings.clone();
     drop(settings);
-    persist_settings(&app, &new_settings)
+    persist_settings(&app, &new_settings)?;
+    Ok(())
 }
 
 #[tauri::command]
@@ -274,36 +704,130 @@ fn reset_prompt_to_default(app: AppHandle, app_state: State<AppState>) -> Result
 }
 
 #[tauri::command]
-fn add_keyword(app: AppHandle, spoken: String, replacement: String, app_state: State<AppState>) -> Result<(), String> {
+fn add_keyword(
+    app: AppHandle,
+    spoken: String,
+    replacement: String,
+    match_mode: Option<MatchMode>,
+    preserve_case: Option<bool>,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    let rule = KeywordRule {
+        spoken,
+        replacement,
+        match_mode: match_mode.unwrap_or_default(),
+        preserve_case: preserve_case.unwrap_or(false),
+        enabled: true,
+    }
+    .normalized();
+    rule.validate()?;
+
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    keywords::upsert(&mut settings.keywords, rule);
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn remove_keyword(
+    app: AppHandle,
+    spoken: String,
+    match_mode: Option<MatchMode>,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    let match_mode = match_mode.unwrap_or_default();
     let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
-    settings.keywords.insert(spoken.to_lowercase(), replacement);
+    settings.keywords.retain(|rule| !rule.is_for(match_mode, &spoken));
     let settings_clone = settings.clone();
     drop(settings);
     persist_settings(&app, &settings_clone)
 }
 
 #[tauri::command]
-fn remove_keyword(app: AppHandle, spoken: String, app_state: State<AppState>) -> Result<(), String> {
+fn set_keyword_enabled(
+    app: AppHandle,
+    spoken: String,
+    match_mode: Option<MatchMode>,
+    enabled: bool,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    let match_mode = match_mode.unwrap_or_default();
     let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
-    settings.keywords.remove(&spoken.to_lowercase());
+    let rule = settings
+        .keywords
+        .iter_mut()
+        .find(|rule| rule.is_for(match_mode, &spoken))
+        .ok_or_else(|| format!("No keyword rule for \"{}\"", spoken))?;
+    rule.enabled = enabled;
     let settings_clone = settings.clone();
     drop(settings);
     persist_settings(&app, &settings_clone)
 }
 
 #[tauri::command]
-fn get_keywords(app_state: State<AppState>) -> Result<HashMap<String, String>, String> {
+fn get_keywords(app_state: State<AppState>) -> Result<Vec<KeywordRule>, String> {
     let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
     Ok(settings.keywords.clone())
 }
 
+// Import a CSV or JSON keyword list. Without a format it is guessed from the content.
+// A replace that would skip rows or leave no rules needs `force`; without it the
+// report comes back with `applied` false and nothing is changed.
 #[tauri::command]
-fn set_hotkey(app: AppHandle, hotkey: String, app_state: State<AppState>) -> Result<(), String> {
+fn import_keywords(
+    app: AppHandle,
+    content: String,
+    format: Option<KeywordFormat>,
+    mode: Option<ImportMode>,
+    force: Option<bool>,
+    app_state: State<AppState>,
+) -> Result<ImportReport, String> {
     let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
-    settings.hotkey = hotkey;
+    let report = keywords::import(
+        &mut settings.keywords,
+        &content,
+        format,
+        mode.unwrap_or_default(),
+        force.unwrap_or(false),
+    )?;
+    if !report.applied {
+        return Ok(report);
+    }
     let settings_clone = settings.clone();
     drop(settings);
-    persist_settings(&app, &settings_clone)
+    persist_settings(&app, &settings_clone)?;
+    Ok(report)
+}
+
+// Save the keyword list to the Downloads folder and return the file's path
+#[tauri::command]
+fn export_keywords(app: AppHandle, format: KeywordFormat, app_state: State<AppState>) -> Result<String, String> {
+    let content = {
+        let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+        keywords::export(&settings.keywords, format)?
+    };
+
+    let dir = app
+        .path()
+        .download_dir()
+        .map_err(|e| format!("Failed to find the Downloads folder: {}", e))?;
+    // Never overwrite an earlier export
+    let path = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("keywords.{}", format.extension())),
+            n => dir.join(format!("keywords-{}.{}", n, format.extension())),
+        })
+        .find(|path| !path.exists())
+        .ok_or("No free file name for the export")?;
+
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+fn set_hotkey(app: AppHandle, hotkey: String, app_state: State<AppState>) -> Result<(), HotkeyError> {
+    update_hotkeys(&app, &app_state, |settings| settings.hotkey = hotkey.trim().to_string())
 }
 
 #[tauri::command]
@@ -312,6 +836,43 @@ fn get_hotkey(app_state: State<AppState>) -> Result<String, String> {
     Ok(settings.hotkey.clone())
 }
 
+#[tauri::command]
+fn set_cancel_hotkey(app: AppHandle, hotkey: Option<String>, app_state: State<AppState>) -> Result<(), HotkeyError> {
+    update_hotkeys(&app, &app_state, |settings| {
+        settings.cancel_hotkey = hotkey.map(|h| h.trim().to_string()).filter(|h| !h.is_empty());
+    })
+}
+
+#[tauri::command]
+fn set_hotkey_mode(app: AppHandle, mode: HotkeyMode, app_state: State<AppState>) -> Result<(), HotkeyError> {
+    update_hotkeys(&app, &app_state, |settings| settings.hotkey_mode = mode)
+}
+
+// Apply a hotkey change, persisting it only once the new hotkeys are registered.
+// On failure the previous hotkeys stay active and saved.
+fn update_hotkeys(app: &AppHandle, app_state: &AppState, change: impl FnOnce(&mut Settings)) -> Result<(), HotkeyError> {
+    let current = app_state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let mut updated = current.clone();
+    change(&mut updated);
+
+    dictation::update_shortcuts(app, &current, &updated)?;
+
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.hotkey = updated.hotkey.clone();
+    settings.hotkey_mode = updated.hotkey_mode;
+    settings.cancel_hotkey = updated.cancel_hotkey.clone();
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(app, &settings_clone)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_cancel_hotkey(app_state: State<AppState>) -> Result<Option<String>, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.cancel_hotkey.clone())
+}
+
 #[tauri::command]
 fn set_auto_paste(app: AppHandle, enabled: bool, app_state: State<AppState>) -> Result<(), String> {
     let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
@@ -321,53 +882,376 @@ fn set_auto_paste(app: AppHandle, enabled: bool, app_state: State<AppState>) ->
     persist_settings(&app, &settings_clone)
 }
 
-// Text injection - copies text to clipboard and simulates paste
 #[tauri::command]
-fn inject_text(text: String) -> Result<(), String> {
-    use arboard::Clipboard;
+fn set_paste_settings(
+    app: AppHandle,
+    injection_mode: InjectionMode,
+    typing_delay_ms: u64,
+    preserve_clipboard: bool,
+    clipboard_restore_delay_ms: u64,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.injection_mode = injection_mode;
+    settings.typing_delay_ms = typing_delay_ms;
+    settings.preserve_clipboard = preserve_clipboard;
+    settings.clipboard_restore_delay_ms = clipboard_restore_delay_ms;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
 
-    // Set clipboard
-    let mut clipboard = Clipboard::new().map_err(|e| format!("Clipboard error: {}", e))?;
-    clipboard.set_text(&text).map_err(|e| format!("Failed to set clipboard: {}", e))?;
+#[tauri::command]
+fn set_input_device(app: AppHandle, device: Option<String>, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.input_device = device.filter(|d| !d.is_empty());
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
 
-    // Spawn a thread to send the keystroke after a delay
-    // This allows the Tauri window to lose focus first
-    std::thread::spawn(move || {
-        use enigo::{Enigo, Keyboard, Settings};
+#[tauri::command]
+fn get_input_device(app_state: State<AppState>) -> Result<Option<String>, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.input_device.clone())
+}
 
-        // Wait for focus to return to the previous app
-        std::thread::sleep(std::time::Duration::from_millis(150));
+#[tauri::command]
+fn set_input_channel(app: AppHandle, channel: Option<u16>, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.input_channel = channel;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
 
-        let mut enigo = match Enigo::new(&Settings::default()) {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("Enigo error: {}", e);
-                return;
-            }
-        };
+#[tauri::command]
+fn get_input_channel(app_state: State<AppState>) -> Result<Option<u16>, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.input_channel)
+}
 
-        #[cfg(target_os = "macos")]
-        {
-            use enigo::Key;
-            // Cmd+V on macOS
-            let _ = enigo.key(Key::Meta, enigo::Direction::Press);
-            let _ = enigo.key(Key::Unicode('v'), enigo::Direction::Click);
-            let _ = enigo.key(Key::Meta, enigo::Direction::Release);
-        }
+#[tauri::command]
+fn set_resample_audio(app: AppHandle, enabled: bool, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.resample_audio = enabled;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            use enigo::Key;
-            // Ctrl+V on Windows/Linux
-            let _ = enigo.key(Key::Control, enigo::Direction::Press);
-            let _ = enigo.key(Key::Unicode('v'), enigo::Direction::Click);
-            let _ = enigo.key(Key::Control, enigo::Direction::Release);
+#[tauri::command]
+fn set_trim_silence(app: AppHandle, enabled: bool, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.trim_silence = enabled;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn set_transcription_backend(app: AppHandle, backend: TranscriptionBackend, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.transcription_backend = backend;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn set_local_model_path(app: AppHandle, path: Option<String>, app_state: State<AppState>) -> Result<(), String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(p) = &path {
+        if !std::path::Path::new(p).is_file() {
+            return Err(format!("Model file not found: {}", p));
         }
-    });
+    }
+
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.local_model_path = path;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
 
+fn validate_endpoint(endpoint: &EndpointConfig) -> Result<(), String> {
+    let base_url = endpoint.base_url.trim();
+    if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+        return Err("Base URL must start with http:// or https://".to_string());
+    }
+    if endpoint.model.trim().is_empty() {
+        return Err("Model name cannot be empty".to_string());
+    }
     Ok(())
 }
 
+#[tauri::command]
+fn set_transcription_endpoint(app: AppHandle, endpoint: EndpointConfig, app_state: State<AppState>) -> Result<(), String> {
+    validate_endpoint(&endpoint)?;
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.transcription_endpoint = endpoint;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn set_formatting_endpoint(app: AppHandle, endpoint: EndpointConfig, app_state: State<AppState>) -> Result<(), String> {
+    validate_endpoint(&endpoint)?;
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.formatting_endpoint = endpoint;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn set_formatting_backend(app: AppHandle, backend: FormattingBackend, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.formatting_backend = backend;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn set_ollama_endpoint(app: AppHandle, endpoint: EndpointConfig, app_state: State<AppState>) -> Result<(), String> {
+    validate_endpoint(&endpoint)?;
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.ollama_endpoint = endpoint;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn set_formatting_model(app: AppHandle, model: String, app_state: State<AppState>) -> Result<(), String> {
+    let model = model.trim().to_string();
+    if model.is_empty() {
+        return Err("Model name cannot be empty".to_string());
+    }
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.formatting_endpoint_mut().model = model;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn get_formatting_model(app_state: State<AppState>) -> Result<String, String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.formatting_endpoint_mut().model.clone())
+}
+
+#[tauri::command]
+fn set_formatting_temperature(app: AppHandle, temperature: f32, app_state: State<AppState>) -> Result<(), String> {
+    if !(0.0..=2.0).contains(&temperature) {
+        return Err("Temperature must be between 0 and 2".to_string());
+    }
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.formatting_temperature = temperature;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn get_formatting_temperature(app_state: State<AppState>) -> Result<f32, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.formatting_temperature)
+}
+
+#[tauri::command]
+fn set_formatting_max_tokens(app: AppHandle, max_tokens: Option<u32>, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.formatting_max_tokens = max_tokens.filter(|&t| t > 0);
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn get_formatting_max_tokens(app_state: State<AppState>) -> Result<Option<u32>, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.formatting_max_tokens)
+}
+
+#[tauri::command]
+fn set_formatting_mode(app: AppHandle, mode: FormattingMode, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.formatting_mode = mode;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+#[tauri::command]
+fn get_formatting_mode(app_state: State<AppState>) -> Result<FormattingMode, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.formatting_mode)
+}
+
+#[tauri::command]
+fn set_auto_format_min_words(app: AppHandle, words: usize, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.auto_format_min_words = words;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+// Timeouts and proxy for API requests. Rejected without saving if the client can't be built.
+#[tauri::command]
+fn set_network_settings(
+    app: AppHandle,
+    connect_timeout_secs: u64,
+    request_timeout_secs: u64,
+    proxy_url: Option<String>,
+    app_state: State<AppState>,
+    http: State<HttpClient>,
+) -> Result<(), String> {
+    if connect_timeout_secs == 0 || request_timeout_secs == 0 {
+        return Err("Timeouts must be at least 1 second".to_string());
+    }
+
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    let mut updated = settings.clone();
+    updated.connect_timeout_secs = connect_timeout_secs;
+    updated.request_timeout_secs = request_timeout_secs;
+    updated.proxy_url = proxy_url.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    http.configure(&updated)?;
+    *settings = updated.clone();
+    drop(settings);
+    persist_settings(&app, &updated)
+}
+
+#[tauri::command]
+fn set_recording_cache_limit(app: AppHandle, limit: usize, app_state: State<AppState>) -> Result<(), String> {
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    settings.recording_cache_limit = limit;
+    let settings_clone = settings.clone();
+    drop(settings);
+    persist_settings(&app, &settings_clone)
+}
+
+// List the models offered by the configured transcription or formatting endpoint
+#[tauri::command]
+async fn list_models(
+    endpoint: ModelEndpoint,
+    app_state: State<'_, AppState>,
+    http: State<'_, HttpClient>,
+) -> Result<Vec<String>, ApiError> {
+    let client = http.get()?;
+    let (endpoint, api_key, is_ollama) = {
+        let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+        let (endpoint, is_ollama) = match endpoint {
+            ModelEndpoint::Transcription => (settings.transcription_endpoint.clone(), false),
+            ModelEndpoint::Formatting => match settings.formatting_backend {
+                FormattingBackend::OpenAi => (settings.formatting_endpoint.clone(), false),
+                FormattingBackend::Ollama => (settings.ollama_endpoint.clone(), true),
+            },
+        };
+        let api_key = endpoint.resolve_api_key(settings.openai_api_key.as_deref());
+        (endpoint, api_key, is_ollama)
+    };
+
+    // Ollama lists its models on a different route
+    if is_ollama {
+        return formatting::list_ollama_models(&client, &endpoint).await;
+    }
+
+    let response = api::send_with_retry("Models", || {
+        Ok(endpoint.apply_headers(client.get(endpoint.url("/models")), api_key.as_deref()))
+    })
+    .await?;
+
+    let models_response: ModelsResponse = api::parse_json("Models", response).await?;
+
+    let mut models: Vec<String> = models_response.data.into_iter().map(|m| m.id).collect();
+    models.sort();
+    Ok(models)
+}
+
+// List available audio input devices with their supported configs
+#[tauri::command]
+fn list_input_devices() -> Result<Vec<InputDeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+    let devices = host.input_devices().map_err(|e| format!("Failed to list input devices: {}", e))?;
+
+    let mut result = Vec::new();
+    for device in devices {
+        let name = match device.name() {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+
+        let configs = device
+            .supported_input_configs()
+            .map(|configs| {
+                configs
+                    .map(|c| InputConfigInfo {
+                        channels: c.channels(),
+                        min_sample_rate: c.min_sample_rate().0,
+                        max_sample_rate: c.max_sample_rate().0,
+                        sample_format: c.sample_format().to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        result.push(InputDeviceInfo {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+            configs,
+        });
+    }
+
+    Ok(result)
+}
+
+// Find an input device by name, falling back to the default device
+fn resolve_input_device(app: &AppHandle, requested: Option<&str>) -> Option<cpal::Device> {
+    let host = cpal::default_host();
+
+    if let Some(name) = requested {
+        let found = host.input_devices().ok().and_then(|mut devices| {
+            devices.find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        });
+        if found.is_some() {
+            return found;
+        }
+
+        let fallback = host.default_input_device();
+        eprintln!("Input device \"{}\" not found, using default", name);
+        let _ = app.emit(
+            "input-device-fallback",
+            InputDeviceFallback {
+                requested: name.to_string(),
+                fallback: fallback.as_ref().and_then(|d| d.name().ok()),
+            },
+        );
+        return fallback;
+    }
+
+    host.default_input_device()
+}
+
+// Text injection - pastes via the clipboard or types the text, depending on settings.
+// Resolves once the keystrokes have been sent, or with an error saying whether the
+// text was left on the clipboard instead.
+#[tauri::command]
+async fn inject_text(text: String, app_state: State<'_, AppState>) -> Result<(), InjectionError> {
+    let options = {
+        let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+        InjectOptions::from_settings(&settings)
+    };
+    tauri::async_runtime::spawn_blocking(move || injection::inject(&text, options))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 // Request necessary permissions (macOS-specific, no-op on other platforms)
 #[tauri::command]
 fn request_permissions() -> Result<(), String> {
@@ -562,7 +1446,16 @@ fn hide_overlay(app: AppHandle) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
+fn start_recording(
+    app: AppHandle,
+    audio_state: State<AudioState>,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    let (input_device, input_channel) = {
+        let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+        (settings.input_device.clone(), settings.input_channel)
+    };
+
     let mut is_recording = audio_state.is_recording.lock().map_err(|e| e.to_string())?;
     if *is_recording {
         return Err("Already recording".to_string());
@@ -581,8 +1474,7 @@ fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
     let sample_rate_clone = audio_state.sample_rate.clone();
 
     std::thread::spawn(move || {
-        let host = cpal::default_host();
-        let device = match host.default_input_device() {
+        let device = match resolve_input_device(&app, input_device.as_deref()) {
             Some(d) => d,
             None => {
                 eprintln!("No input device available");
@@ -599,12 +1491,13 @@ fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
         };
 
         // Store sample rate
-        {
-            if let Ok(mut sr) = sample_rate_clone.lock() {
-                *sr = config.sample_rate().0;
-            }
+        if let Ok(mut sr) = sample_rate_clone.lock() {
+            *sr = config.sample_rate().0;
         }
 
+        let channels = config.channels() as usize;
+        let selected_channel = input_channel.map(|c| c as usize);
+
         let err_fn = |err| eprintln!("Audio stream error: {}", err);
 
         let audio_data = audio_data_clone.clone();
@@ -614,13 +1507,7 @@ fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
             cpal::SampleFormat::F32 => device.build_input_stream(
                 &config.into(),
                 move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    if let Ok(recording) = is_recording_check.lock() {
-                        if *recording {
-                            if let Ok(mut audio) = audio_data.lock() {
-                                audio.extend_from_slice(data);
-                            }
-                        }
-                    }
+                    push_samples(&is_recording_check, &audio_data, data, channels, selected_channel);
                 },
                 err_fn,
                 None,
@@ -628,15 +1515,11 @@ fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
             cpal::SampleFormat::I16 => device.build_input_stream(
                 &config.into(),
                 move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                    if let Ok(recording) = is_recording_check.lock() {
-                        if *recording {
-                            if let Ok(mut audio) = audio_data.lock() {
-                                for &sample in data {
-                                    audio.push(sample as f32 / i16::MAX as f32);
-                                }
-                            }
-                        }
-                    }
+                    let samples: Vec<f32> = data
+                        .iter()
+                        .map(|&sample| sample as f32 / i16::MAX as f32)
+                        .collect();
+                    push_samples(&is_recording_check, &audio_data, &samples, channels, selected_channel);
                 },
                 err_fn,
                 None,
@@ -644,15 +1527,11 @@ fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
             cpal::SampleFormat::U16 => device.build_input_stream(
                 &config.into(),
                 move |data: &[u16], _: &cpal::InputCallbackInfo| {
-                    if let Ok(recording) = is_recording_check.lock() {
-                        if *recording {
-                            if let Ok(mut audio) = audio_data.lock() {
-                                for &sample in data {
-                                    audio.push((sample as f32 / u16::MAX as f32) * 2.0 - 1.0);
-                                }
-                            }
-                        }
-                    }
+                    let samples: Vec<f32> = data
+                        .iter()
+                        .map(|&sample| (sample as f32 / u16::MAX as f32) * 2.0 - 1.0)
+                        .collect();
+                    push_samples(&is_recording_check, &audio_data, &samples, channels, selected_channel);
                 },
                 err_fn,
                 None,
@@ -690,6 +1569,23 @@ fn start_recording(audio_state: State<AudioState>) -> Result<(), String> {
     Ok(())
 }
 
+// Append interleaved samples from the input callback to the buffer as mono
+fn push_samples(
+    is_recording: &Arc<Mutex<bool>>,
+    audio_data: &Arc<Mutex<Vec<f32>>>,
+    samples: &[f32],
+    channels: usize,
+    selected_channel: Option<usize>,
+) {
+    if let Ok(recording) = is_recording.lock() {
+        if *recording {
+            if let Ok(mut audio) = audio_data.lock() {
+                audio.extend(audio::downmix_to_mono(samples, channels, selected_channel));
+            }
+        }
+    }
+}
+
 #[tauri::command]
 fn stop_recording(audio_state: State<AudioState>) -> Result<(), String> {
     let mut is_recording = audio_state.is_recording.lock().map_err(|e| e.to_string())?;
@@ -697,6 +1593,22 @@ fn stop_recording(audio_state: State<AudioState>) -> Result<(), String> {
     Ok(())
 }
 
+// Record button equivalents of the hotkey: the same flow, including auto-paste
+#[tauri::command]
+fn start_dictation(app: AppHandle) {
+    dictation::start(&app);
+}
+
+#[tauri::command]
+fn stop_dictation(app: AppHandle) {
+    dictation::stop(&app);
+}
+
+#[tauri::command]
+fn cancel_dictation(app: AppHandle) {
+    dictation::cancel(&app);
+}
+
 #[tauri::command]
 fn is_recording(audio_state: State<AudioState>) -> Result<bool, String> {
     let is_recording = audio_state.is_recording.lock().map_err(|e| e.to_string())?;
@@ -707,16 +1619,13 @@ fn is_recording(audio_state: State<AudioState>) -> Result<bool, String> {
 async fn transcribe_audio(
     audio_state: State<'_, AudioState>,
     app_state: State<'_, AppState>,
-) -> Result<TranscriptionResult, String> {
-    // Get settings
-    let (api_key, custom_prompt, keywords) = {
-        let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
-        (
-            settings.openai_api_key.clone().ok_or("OpenAI API key not configured")?,
-            settings.custom_prompt.clone(),
-            settings.keywords.clone(),
-        )
-    };
+    history: State<'_, HistoryState>,
+    recordings: State<'_, RecordingCache>,
+    transcription_state: State<'_, TranscriptionState>,
+    http: State<'_, HttpClient>,
+) -> Result<TranscriptionResult, ApiError> {
+    let cancel_rx = transcription_state.begin()?;
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?.clone();
 
     // Get audio data
     let (audio_data, sample_rate) = {
@@ -726,43 +1635,243 @@ async fn transcribe_audio(
     };
 
     if audio_data.is_empty() {
-        return Err("No audio recorded".to_string());
+        return Err(ApiError::Audio {
+            message: "No audio recorded".to_string(),
+        });
+    }
+
+    // Buffer is already downmixed to mono by the capture callback
+    println!("Captured {} samples at {} Hz", audio_data.len(), sample_rate);
+
+    let (audio_data, sample_rate) = prepare_audio(audio_data, sample_rate, settings.resample_audio);
+
+    // Keep the audio before any API call so it survives a failure and the next recording
+    let recording_id = match recordings.save(&audio_data, sample_rate, settings.recording_cache_limit, &history) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Failed to cache recording: {}", e);
+            None
+        }
+    };
+
+    run_cancellable(cancel_rx, &settings, http.get()?, audio_data, sample_rate, recording_id, &history).await
+}
+
+// Settings to swap in when re-transcribing a cached recording
+#[derive(Deserialize, Default)]
+pub struct PipelineOverrides {
+    transcription_backend: Option<TranscriptionBackend>,
+    formatting_backend: Option<FormattingBackend>,
+    formatting_mode: Option<FormattingMode>,
+    custom_prompt: Option<String>,
+}
+
+impl PipelineOverrides {
+    fn apply(self, settings: &mut Settings) {
+        if let Some(backend) = self.transcription_backend {
+            settings.transcription_backend = backend;
+        }
+        if let Some(backend) = self.formatting_backend {
+            settings.formatting_backend = backend;
+        }
+        if let Some(mode) = self.formatting_mode {
+            settings.formatting_mode = mode;
+        }
+        if let Some(prompt) = self.custom_prompt {
+            settings.custom_prompt = prompt;
+        }
     }
+}
 
-    // Convert to WAV
-    let wav_data = create_wav(&audio_data, sample_rate)?;
+// Run a cached recording through the pipeline again, optionally with different settings
+#[tauri::command]
+async fn retranscribe(
+    id: String,
+    overrides: Option<PipelineOverrides>,
+    app_state: State<'_, AppState>,
+    history: State<'_, HistoryState>,
+    recordings: State<'_, RecordingCache>,
+    transcription_state: State<'_, TranscriptionState>,
+    http: State<'_, HttpClient>,
+) -> Result<TranscriptionResult, ApiError> {
+    let cancel_rx = transcription_state.begin()?;
+    let mut settings = app_state.settings.lock().map_err(|e| e.to_string())?.clone();
+    if let Some(overrides) = overrides {
+        overrides.apply(&mut settings);
+    }
 
-    // Send to Whisper API
-    let raw_text = transcribe_with_whisper(&api_key, wav_data).await?;
+    let (audio_data, sample_rate) = recordings.load(&id)?;
+    println!("Re-transcribing recording {} ({} samples at {} Hz)", id, audio_data.len(), sample_rate);
+
+    let (audio_data, sample_rate) = prepare_audio(audio_data, sample_rate, settings.resample_audio);
+    run_cancellable(cancel_rx, &settings, http.get()?, audio_data, sample_rate, Some(id), &history).await
+}
+
+// Abort the transcription in progress. Returns false if there was nothing to cancel.
+#[tauri::command]
+fn cancel_transcription(transcription_state: State<TranscriptionState>) -> Result<bool, String> {
+    transcription_state.cancel()
+}
+
+#[tauri::command]
+fn list_recordings(recordings: State<RecordingCache>) -> Result<Vec<RecordingInfo>, String> {
+    recordings.list()
+}
+
+// Downsample to what Whisper actually uses to keep the upload small
+fn prepare_audio(audio_data: Vec<f32>, sample_rate: u32, resample_audio: bool) -> (Vec<f32>, u32) {
+    if resample_audio && sample_rate != audio::WHISPER_SAMPLE_RATE {
+        (
+            audio::resample(&audio_data, sample_rate, audio::WHISPER_SAMPLE_RATE),
+            audio::WHISPER_SAMPLE_RATE,
+        )
+    } else {
+        (audio_data, sample_rate)
+    }
+}
+
+// Run the pipeline unless it's cancelled first. Dropping the pipeline future aborts any
+// request still in flight; a local whisper.cpp run finishes in the background and is discarded.
+async fn run_cancellable(
+    cancel_rx: tokio::sync::oneshot::Receiver<()>,
+    settings: &Settings,
+    client: reqwest::Client,
+    audio_data: Vec<f32>,
+    sample_rate: u32,
+    recording_id: Option<String>,
+    history: &HistoryState,
+) -> Result<TranscriptionResult, ApiError> {
+    tokio::select! {
+        result = run_pipeline(settings, client, audio_data, sample_rate, recording_id.clone(), history) => result,
+        Ok(()) = cancel_rx => {
+            println!("Transcription cancelled");
+            Ok(TranscriptionResult {
+                outcome: TranscriptionOutcome::Cancelled,
+                raw_text: String::new(),
+                formatted_text: String::new(),
+                formatting: FormattingPath::None,
+                guardrail_triggered: false,
+                formatting_error: None,
+                history_id: None,
+                recording_id,
+            })
+        }
+    }
+}
+
+// Speech-to-text, keyword replacement and formatting for mono audio, recorded to history
+async fn run_pipeline(
+    settings: &Settings,
+    client: reqwest::Client,
+    audio_data: Vec<f32>,
+    sample_rate: u32,
+    recording_id: Option<String>,
+    history: &HistoryState,
+) -> Result<TranscriptionResult, ApiError> {
+    let provider = transcription::create_provider(settings, client.clone())?;
+    let formatter = formatting::create_provider(settings, client);
+    let keywords = &settings.keywords;
+    let duration_ms = audio_data.len() as i64 * 1000 / sample_rate.max(1) as i64;
+
+    // Drop silence around the speech; silent clips make Whisper hallucinate text
+    let audio_data = if settings.trim_silence {
+        match audio::detect_speech(&audio_data, sample_rate) {
+            Some(range) => audio_data[range].to_vec(),
+            None => {
+                println!("No speech detected, skipping transcription");
+                return Ok(TranscriptionResult {
+                    outcome: TranscriptionOutcome::NoSpeech,
+                    raw_text: String::new(),
+                    formatted_text: String::new(),
+                    formatting: FormattingPath::None,
+                    guardrail_triggered: false,
+                    formatting_error: None,
+                    history_id: None,
+                    recording_id,
+                });
+            }
+        }
+    } else {
+        audio_data
+    };
+
+    // Send to the configured speech-to-text provider
+    println!("Transcribing with {}...", provider.name());
+    let raw_text = provider.transcribe(&audio_data, sample_rate).await?;
 
     // Apply keyword replacements to raw text before GPT processing
-    let processed_text = apply_keywords(&raw_text, &keywords);
+    let processed_text = keywords::apply(&raw_text, keywords);
+
+    // Format using the custom prompt. Without a usable formatter (e.g. fully offline
+    // with no API key) the keyword-processed text is used as-is.
+    let mut formatting = choose_formatting_path(settings.formatting_mode, settings.auto_format_min_words, &processed_text, formatter.is_some());
+    let mut guardrail_triggered = false;
+    let mut formatting_error = None;
+    let mut profile = "raw".to_string();
+    let formatted_text = match (&formatter, formatting) {
+        (Some(formatter), FormattingPath::Formatted) => {
+            println!("Formatting with {}...", formatter.name());
+            let system_prompt = formatting::build_system_prompt(&settings.custom_prompt, keywords);
+            match formatter.format(&system_prompt, &processed_text).await {
+                Ok(formatted) => {
+                    println!("Formatting complete: {} chars", formatted.len());
+
+                    // Fall back to the raw text if the model answered instead of formatting
+                    match formatting::check_formatted_output(&processed_text, &formatted) {
+                        Ok(()) => {
+                            profile = formatter.name();
+                            formatted
+                        }
+                        Err(reason) => {
+                            println!("Discarding formatter output: {}", reason);
+                            guardrail_triggered = true;
+                            processed_text
+                        }
+                    }
+                }
+                // The transcript is still worth keeping, e.g. local Whisper while offline
+                Err(e) => {
+                    eprintln!("Formatting failed, using the unformatted text: {}", e);
+                    formatting = FormattingPath::FormatterFailed;
+                    formatting_error = Some(e);
+                    processed_text
+                }
+            }
+        }
+        _ => {
+            println!("Skipping formatting");
+            processed_text
+        }
+    };
 
-    // Format with GPT using custom prompt
-    println!("Calling GPT for formatting...");
-    let formatted_text = format_with_gpt(&api_key, &processed_text, &custom_prompt, &keywords).await?;
-    println!("GPT formatting complete: {} chars", formatted_text.len());
+    // A history failure shouldn't lose the transcript the user is waiting for
+    let history_id = match history.insert(NewHistoryEntry {
+        duration_ms,
+        raw_text: raw_text.clone(),
+        formatted_text: formatted_text.clone(),
+        profile,
+        provider: provider.name(),
+        recording_id: recording_id.clone(),
+    }) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            eprintln!("Failed to save transcription to history: {}", e);
+            None
+        }
+    };
 
     Ok(TranscriptionResult {
+        outcome: TranscriptionOutcome::Transcribed,
         raw_text,
         formatted_text,
+        formatting,
+        guardrail_triggered,
+        formatting_error,
+        history_id,
+        recording_id,
     })
 }
 
-fn apply_keywords(text: &str, keywords: &HashMap<String, String>) -> String {
-    let mut result = text.to_string();
-    for (spoken, replacement) in keywords {
-        // Case-insensitive replacement
-        let re = regex::RegexBuilder::new(&regex::escape(spoken))
-            .case_insensitive(true)
-            .build();
-        if let Ok(re) = re {
-            result = re.replace_all(&result, replacement.as_str()).to_string();
-        }
-    }
-    result
-}
-
 fn create_wav(audio_data: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
     let spec = hound::WavSpec {
         channels: 1,
@@ -791,103 +1900,46 @@ fn create_wav(audio_data: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
     Ok(cursor.into_inner())
 }
 
-async fn transcribe_with_whisper(api_key: &str, wav_data: Vec<u8>) -> Result<String, String> {
-    let client = reqwest::Client::new();
-
-    let part = reqwest::multipart::Part::bytes(wav_data)
-        .file_name("audio.wav")
-        .mime_str("audio/wav")
-        .map_err(|e| e.to_string())?;
-
-    let form = reqwest::multipart::Form::new()
-        .text("model", "whisper-1")
-        .text("response_format", "json")
-        .part("file", part);
-
-    let response = client
-        .post("https://api.openai.com/v1/audio/transcriptions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Whisper API error: {}", error_text));
+// Decide whether a transcript should go through the formatter
+fn choose_formatting_path(mode: FormattingMode, auto_min_words: usize, text: &str, formatter_available: bool) -> FormattingPath {
+    match mode {
+        FormattingMode::Off => FormattingPath::SkippedDisabled,
+        FormattingMode::Auto if text.split_whitespace().count() < auto_min_words => {
+            FormattingPath::SkippedShort
+        }
+        _ if !formatter_available => FormattingPath::SkippedNoApiKey,
+        _ => FormattingPath::Formatted,
     }
+}
 
-    let whisper_response: WhisperResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Parse error: {}", e))?;
-
-    Ok(whisper_response.text)
-}
-
-async fn format_with_gpt(
-    api_key: &str,
-    raw_text: &str,
-    custom_prompt: &str,
-    keywords: &HashMap<String, String>,
-) -> Result<String, String> {
-    let client = reqwest::Client::new();
-
-    // Build keyword instruction if there are keywords
-    let keyword_instruction = if !keywords.is_empty() {
-        let keyword_list: Vec<String> = keywords
-            .iter()
-            .map(|(k, v)| format!("\"{}\" -> \"{}\"", k, v))
-            .collect();
-        format!(
-            "\n\nIMPORTANT: Apply these exact keyword replacements (case-insensitive):\n{}",
-            keyword_list.join("\n")
-        )
-    } else {
-        String::new()
-    };
-
-    let full_prompt = format!("{}{}", custom_prompt, keyword_instruction);
-
-    let request = ChatRequest {
-        model: "gpt-5.2".to_string(),
-        messages: vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: full_prompt,
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: raw_text.to_string(),
-            },
-        ],
-        temperature: 0.3,
-    };
-
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+// History of past transcriptions, newest first
+#[tauri::command]
+fn list_history(
+    history: State<HistoryState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<HistoryEntry>, String> {
+    history.list(limit.unwrap_or(50), offset.unwrap_or(0))
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Chat API error: {}", error_text));
-    }
+#[tauri::command]
+fn get_history_item(history: State<HistoryState>, id: i64) -> Result<Option<HistoryEntry>, String> {
+    history.get(id)
+}
 
-    let chat_response: ChatResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Parse error: {}", e))?;
+#[tauri::command]
+fn delete_history_item(history: State<HistoryState>, id: i64) -> Result<bool, String> {
+    history.delete(id)
+}
 
-    chat_response
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .ok_or_else(|| "No response from AI".to_string())
+// Full-text search across raw and formatted transcripts
+#[tauri::command]
+fn search_history(
+    history: State<HistoryState>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<HistoryEntry>, String> {
+    history.search(&query, limit.unwrap_or(50))
 }
 
 // Update info response
@@ -949,6 +2001,10 @@ pub fn run() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .manage(AudioState::default())
         .manage(AppState::default())
+        .manage(HistoryState::default())
+        .manage(RecordingCache::default())
+        .manage(TranscriptionState::default())
+        .manage(HttpClient::default())
         .setup(|app| {
             // Load persisted settings
             let persisted = load_persisted_settings(app.handle());
@@ -957,6 +2013,32 @@ pub fn run() {
                 *settings = persisted;
             }
 
+            // Apply the saved timeouts and proxy; a bad proxy URL falls back to a direct connection
+            let http: State<HttpClient> = app.state();
+            if let Ok(settings) = app_state.settings.lock() {
+                if let Err(e) = http.configure(&settings) {
+                    eprintln!("{}", e);
+                }
+                if let Err(e) = dictation::register_shortcuts(app.handle(), &settings) {
+                    eprintln!("{}", e);
+                }
+            }
+
+            // Open the transcription history and recording cache; the app still works without them
+            let history: State<HistoryState> = app.state();
+            let recordings: State<RecordingCache> = app.state();
+            match app.path().app_data_dir() {
+                Ok(dir) => {
+                    if let Err(e) = history.open(&dir.join("history.db")) {
+                        eprintln!("{}", e);
+                    }
+                    if let Err(e) = recordings.open(&dir.join("recordings")) {
+                        eprintln!("{}", e);
+                    }
+                }
+                Err(e) => eprintln!("Failed to resolve app data directory: {}", e),
+            }
+
             // Create system tray
             let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let show = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
@@ -994,14 +2076,57 @@ pub fn run() {
             add_keyword,
             remove_keyword,
             get_keywords,
+            set_keyword_enabled,
+            import_keywords,
+            export_keywords,
             set_hotkey,
             get_hotkey,
+            set_cancel_hotkey,
+            get_cancel_hotkey,
+            set_hotkey_mode,
             set_auto_paste,
+            set_paste_settings,
+            set_input_device,
+            get_input_device,
+            set_input_channel,
+            get_input_channel,
+            set_resample_audio,
+            set_trim_silence,
+            set_transcription_backend,
+            set_local_model_path,
+            set_transcription_endpoint,
+            set_formatting_endpoint,
+            set_formatting_backend,
+            set_ollama_endpoint,
+            set_formatting_model,
+            get_formatting_model,
+            set_formatting_temperature,
+            get_formatting_temperature,
+            set_formatting_max_tokens,
+            get_formatting_max_tokens,
+            list_models,
+            set_formatting_mode,
+            get_formatting_mode,
+            set_auto_format_min_words,
+            set_recording_cache_limit,
+            set_network_settings,
             // Recording
+            list_input_devices,
             start_recording,
             stop_recording,
+            start_dictation,
+            stop_dictation,
+            cancel_dictation,
             is_recording,
             transcribe_audio,
+            retranscribe,
+            cancel_transcription,
+            list_recordings,
+            // History
+            list_history,
+            get_history_item,
+            delete_history_item,
+            search_history,
             // Text injection
             inject_text,
             // Overlay
@@ -0,0 +1,117 @@
+// Minimal HTTP server for testing API clients against canned responses
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// What the server received
+pub struct Captured {
+    pub request_line: String,
+    pub headers: HashMap<String, String>,
+    pub body: Value,
+}
+
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl MockResponse {
+    pub fn json(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Captured>>>,
+}
+
+impl MockServer {
+    // Serve `responses` in order, one per connection, repeating the last one once
+    // they run out. The server runs until the test's runtime shuts down.
+    pub async fn start(responses: Vec<MockResponse>) -> Self {
+        assert!(!responses.is_empty(), "mock server needs a response");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let captured = requests.clone();
+        tokio::spawn(async move {
+            for i in 0.. {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let request = read_request(&mut socket).await;
+                captured.lock().unwrap().push(request);
+
+                let response = &responses[i.min(responses.len() - 1)];
+                let mut head = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    response.status,
+                    response.body.len()
+                );
+                for (name, value) in &response.headers {
+                    head.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                head.push_str("\r\n");
+                socket.write_all(head.as_bytes()).await.unwrap();
+                socket.write_all(response.body.as_bytes()).await.unwrap();
+                socket.shutdown().await.ok();
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    // Requests received so far
+    pub fn requests(&self) -> std::sync::MutexGuard<'_, Vec<Captured>> {
+        self.requests.lock().unwrap()
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Captured {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let header_end = loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        assert!(n > 0, "connection closed before headers were sent");
+        data.extend_from_slice(&buf[..n]);
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default().to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let length: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    while data.len() < header_end + length {
+        let n = socket.read(&mut buf).await.unwrap();
+        assert!(n > 0, "connection closed before body was sent");
+        data.extend_from_slice(&buf[..n]);
+    }
+    let body = serde_json::from_slice(&data[header_end..header_end + length]).unwrap_or(Value::Null);
+
+    Captured {
+        request_line,
+        headers,
+        body,
+    }
+}
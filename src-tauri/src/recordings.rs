@@ -0,0 +1,185 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::api::ApiError;
+use crate::history::HistoryState;
+
+// On-disk cache of recent recordings, so a transcript can be redone after an API
+// failure or with different settings. Files are named by their recording id, which
+// is the capture time in Unix milliseconds.
+#[derive(Default)]
+pub struct RecordingCache {
+    dir: Mutex<Option<PathBuf>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RecordingInfo {
+    pub id: String,
+    // Unix time in milliseconds
+    pub created_at: i64,
+    pub duration_ms: i64,
+}
+
+impl RecordingCache {
+    pub fn open(&self, dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create recordings directory: {}", e))?;
+        let mut guard = self.dir.lock().map_err(|e| e.to_string())?;
+        *guard = Some(dir.to_path_buf());
+        Ok(())
+    }
+
+    fn dir(&self) -> Result<PathBuf, String> {
+        let guard = self.dir.lock().map_err(|e| e.to_string())?;
+        guard.clone().ok_or_else(|| "Recording cache is not available".to_string())
+    }
+
+    // Write a recording and drop the oldest ones beyond `limit`, clearing them from
+    // `history` as well. A limit of 0 disables the cache and returns None.
+    pub fn save(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        limit: usize,
+        history: &HistoryState,
+    ) -> Result<Option<String>, String> {
+        if limit == 0 {
+            return Ok(None);
+        }
+
+        let dir = self.dir()?;
+        let mut created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        // Two recordings in the same millisecond would otherwise share a file
+        while dir.join(format!("{}.wav", created_at)).exists() {
+            created_at += 1;
+        }
+
+        let id = created_at.to_string();
+        let wav = crate::create_wav(samples, sample_rate)?;
+        std::fs::write(dir.join(format!("{}.wav", id)), wav)
+            .map_err(|e| format!("Failed to save recording: {}", e))?;
+
+        self.prune(limit, history)?;
+        Ok(Some(id))
+    }
+
+    // Decode a cached recording back to mono samples and its sample rate
+    pub fn load(&self, id: &str) -> Result<(Vec<f32>, u32), ApiError> {
+        let unavailable = |message: String| ApiError::RecordingUnavailable { message };
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(unavailable(format!("Invalid recording id: {}", id)));
+        }
+
+        let path = self.dir().map_err(unavailable)?.join(format!("{}.wav", id));
+        if !path.exists() {
+            return Err(unavailable(format!("Recording {} is no longer cached", id)));
+        }
+
+        let mut reader = hound::WavReader::open(&path).map_err(|e| ApiError::Audio {
+            message: format!("WAV error: {}", e),
+        })?;
+        let sample_rate = reader.spec().sample_rate;
+        let samples = reader
+            .samples::<i16>()
+            .map(|s| s.map(|s| s as f32 / i16::MAX as f32))
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|e| ApiError::Audio {
+                message: format!("WAV read error: {}", e),
+            })?;
+
+        Ok((samples, sample_rate))
+    }
+
+    // Cached recordings, newest first
+    pub fn list(&self) -> Result<Vec<RecordingInfo>, String> {
+        let mut recordings: Vec<RecordingInfo> = self
+            .ids()?
+            .into_iter()
+            .filter_map(|(created_at, path)| {
+                let reader = hound::WavReader::open(&path).ok()?;
+                let duration_ms = reader.duration() as i64 * 1000 / reader.spec().sample_rate.max(1) as i64;
+                Some(RecordingInfo {
+                    id: created_at.to_string(),
+                    created_at,
+                    duration_ms,
+                })
+            })
+            .collect();
+        recordings.reverse();
+        Ok(recordings)
+    }
+
+    fn prune(&self, limit: usize, history: &HistoryState) -> Result<(), String> {
+        let ids = self.ids()?;
+        let excess = ids.len().saturating_sub(limit);
+        let mut removed = Vec::new();
+        for (id, path) in ids.into_iter().take(excess) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed.push(id.to_string()),
+                Err(e) => eprintln!("Failed to remove cached recording {}: {}", path.display(), e),
+            }
+        }
+        if let Err(e) = history.clear_recordings(&removed) {
+            eprintln!("Failed to clear evicted recordings from history: {}", e);
+        }
+        Ok(())
+    }
+
+    // Cached recordings as (id, path), oldest first
+    fn ids(&self) -> Result<Vec<(i64, PathBuf)>, String> {
+        let entries = std::fs::read_dir(self.dir()?).map_err(|e| e.to_string())?;
+        let mut ids: Vec<(i64, PathBuf)> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "wav" {
+                    return None;
+                }
+                let id = path.file_stem()?.to_str()?.parse().ok()?;
+                Some((id, path))
+            })
+            .collect();
+        ids.sort_by_key(|(id, _)| *id);
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::NewHistoryEntry;
+
+    #[test]
+    fn prune_clears_evicted_recordings_from_history() {
+        let dir = std::env::temp_dir().join(format!("recordings-test-{}", std::process::id()));
+        let history = HistoryState::default();
+        history.open(&dir.join("history.db")).unwrap();
+        let cache = RecordingCache::default();
+        cache.open(&dir.join("recordings")).unwrap();
+
+        let samples = vec![0.0; 160];
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let id = cache.save(&samples, 16000, 3, &history).unwrap().unwrap();
+            history
+                .insert(NewHistoryEntry {
+                    duration_ms: 10,
+                    raw_text: "hello".to_string(),
+                    formatted_text: "Hello.".to_string(),
+                    profile: "default".to_string(),
+                    provider: "test".to_string(),
+                    recording_id: Some(id.clone()),
+                })
+                .unwrap();
+            ids.push(id);
+        }
+        // Evicts the first recording
+        cache.save(&samples, 16000, 3, &history).unwrap();
+
+        let cached: Vec<String> = history.list(10, 0).unwrap().into_iter().filter_map(|e| e.recording_id).collect();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(cached, vec![ids[2].clone(), ids[1].clone()]);
+    }
+}
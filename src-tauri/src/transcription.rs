@@ -0,0 +1,206 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{self, ApiError};
+use crate::{EndpointConfig, Settings};
+
+// Which engine turns recorded audio into text
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionBackend {
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    // whisper.cpp running on this machine, no network needed
+    Local,
+}
+
+// A speech-to-text engine. Implementations receive mono f32 samples and
+// handle their own encoding and transport.
+#[async_trait]
+pub trait TranscriptionProvider: Send + Sync {
+    // Identifier for logs and results, e.g. "openai:whisper-1"
+    fn name(&self) -> String;
+
+    async fn transcribe(&self, samples: &[f32], sample_rate: u32) -> Result<String, ApiError>;
+}
+
+// Build the provider selected in settings
+pub fn create_provider(settings: &Settings, client: reqwest::Client) -> Result<Box<dyn TranscriptionProvider>, ApiError> {
+    match settings.transcription_backend {
+        TranscriptionBackend::OpenAi => {
+            let endpoint = settings.transcription_endpoint.clone();
+            let api_key = endpoint.resolve_api_key(settings.openai_api_key.as_deref());
+            if api_key.is_none() && endpoint.requires_api_key() {
+                return Err(ApiError::Auth {
+                    message: "OpenAI API key not configured".to_string(),
+                });
+            }
+            Ok(Box::new(OpenAiProvider {
+                client,
+                api_key,
+                endpoint,
+            }))
+        }
+        TranscriptionBackend::Local => create_local_provider(settings).map_err(|message| ApiError::LocalModel { message }),
+    }
+}
+
+#[cfg(feature = "local-whisper")]
+fn create_local_provider(settings: &Settings) -> Result<Box<dyn TranscriptionProvider>, String> {
+    let model_path = settings
+        .local_model_path
+        .clone()
+        .ok_or("No local Whisper model selected")?;
+    Ok(Box::new(local::LocalWhisperProvider::new(model_path)))
+}
+
+#[cfg(not(feature = "local-whisper"))]
+fn create_local_provider(_settings: &Settings) -> Result<Box<dyn TranscriptionProvider>, String> {
+    Err("This build doesn't include local transcription support".to_string())
+}
+
+#[derive(Serialize, Deserialize)]
+struct WhisperResponse {
+    text: String,
+}
+
+// OpenAI's Whisper API, or any server exposing the same /audio/transcriptions route
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    api_key: Option<String>,
+    endpoint: EndpointConfig,
+}
+
+#[async_trait]
+impl TranscriptionProvider for OpenAiProvider {
+    fn name(&self) -> String {
+        format!("openai:{}", self.endpoint.model)
+    }
+
+    async fn transcribe(&self, samples: &[f32], sample_rate: u32) -> Result<String, ApiError> {
+        let wav_data = crate::create_wav(samples, sample_rate).map_err(|message| ApiError::Audio { message })?;
+        transcribe_with_whisper(&self.client, &self.endpoint, self.api_key.as_deref(), wav_data).await
+    }
+}
+
+async fn transcribe_with_whisper(
+    client: &reqwest::Client,
+    endpoint: &EndpointConfig,
+    api_key: Option<&str>,
+    wav_data: Vec<u8>,
+) -> Result<String, ApiError> {
+    let response = api::send_with_retry("Whisper", || {
+        let part = reqwest::multipart::Part::bytes(wav_data.clone())
+            .file_name("audio.wav")
+            .mime_str("audio/wav")
+            .map_err(|e| e.to_string())?;
+
+        let form = reqwest::multipart::Form::new()
+            .text("model", endpoint.model.clone())
+            .text("response_format", "json")
+            .part("file", part);
+
+        Ok(endpoint
+            .apply_headers(client.post(endpoint.url("/audio/transcriptions")), api_key)
+            .multipart(form))
+    })
+    .await?;
+
+    let whisper_response: WhisperResponse = api::parse_json("Whisper", response).await?;
+    Ok(whisper_response.text)
+}
+
+#[cfg(feature = "local-whisper")]
+mod local {
+    use super::TranscriptionProvider;
+    use crate::api::ApiError;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    // Loaded model, kept around so only the first transcription pays the load cost
+    static MODEL_CACHE: Mutex<Option<(String, Arc<WhisperContext>)>> = Mutex::new(None);
+
+    // Runs a GGML Whisper model on the CPU via whisper.cpp
+    pub struct LocalWhisperProvider {
+        model_path: String,
+    }
+
+    impl LocalWhisperProvider {
+        pub fn new(model_path: String) -> Self {
+            Self { model_path }
+        }
+    }
+
+    #[async_trait]
+    impl TranscriptionProvider for LocalWhisperProvider {
+        fn name(&self) -> String {
+            let file_name = std::path::Path::new(&self.model_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| self.model_path.clone());
+            format!("local:{}", file_name)
+        }
+
+        async fn transcribe(&self, samples: &[f32], sample_rate: u32) -> Result<String, ApiError> {
+            // whisper.cpp only accepts 16 kHz input
+            let samples = crate::audio::resample(samples, sample_rate, crate::audio::WHISPER_SAMPLE_RATE);
+            let model_path = self.model_path.clone();
+
+            tokio::task::spawn_blocking(move || run_model(&model_path, &samples))
+                .await
+                .map_err(|e| format!("Local transcription task failed: {}", e))
+                .and_then(|result| result)
+                .map_err(|message| ApiError::LocalModel { message })
+        }
+    }
+
+    fn load_model(model_path: &str) -> Result<Arc<WhisperContext>, String> {
+        let mut cache = MODEL_CACHE.lock().map_err(|e| e.to_string())?;
+        if let Some((path, ctx)) = cache.as_ref() {
+            if path == model_path {
+                return Ok(ctx.clone());
+            }
+        }
+
+        if !std::path::Path::new(model_path).is_file() {
+            return Err(format!("Whisper model not found: {}", model_path));
+        }
+
+        let mut params = WhisperContextParameters::default();
+        params.use_gpu(false);
+        let ctx = WhisperContext::new_with_params(model_path, params)
+            .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+        let ctx = Arc::new(ctx);
+        *cache = Some((model_path.to_string(), ctx.clone()));
+        Ok(ctx)
+    }
+
+    fn run_model(model_path: &str, samples: &[f32]) -> Result<String, String> {
+        let ctx = load_model(model_path)?;
+        let mut state = ctx
+            .create_state()
+            .map_err(|e| format!("Failed to create Whisper state: {}", e))?;
+
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get().min(8))
+            .unwrap_or(4);
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_n_threads(threads as i32);
+        params.set_language(Some("auto"));
+        params.set_no_context(true);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+
+        state
+            .full(params, samples)
+            .map_err(|e| format!("Local transcription failed: {}", e))?;
+
+        let text: String = state.as_iter().map(|segment| segment.to_string()).collect();
+        Ok(text.trim().to_string())
+    }
+}
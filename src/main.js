@@ -1,24 +1,27 @@
 const { invoke } = window.__TAURI__.core;
-
-// Import global shortcut plugin
-let globalShortcut;
-try {
-  globalShortcut = window.__TAURI__['global-shortcut'] || window.__TAURI__.globalShortcut;
-} catch (e) {
-  console.log('Global shortcut not available:', e);
-}
+const { listen } = window.__TAURI__.event;
 
 // DOM elements - Record page
 let apiKeyInput, saveKeyBtn, keyStatus;
 let recordBtn, recordText, recordingIndicator;
 let rawTextEl, formattedTextEl, copyBtn, pasteBtn;
-let errorMsg, loadingEl;
+let errorMsg, loadingEl, cancelBtn;
 let autoPasteCheckbox, hotkeyDisplay;
 
 // DOM elements - Settings page
-let hotkeyInput, saveHotkeyBtn, hotkeyStatus;
-let keywordSpoken, keywordReplacement, addKeywordBtn, keywordsList;
+let hotkeyInput, saveHotkeyBtn, hotkeyStatus, hotkeyModeSelect;
+let cancelHotkeyInput, saveCancelHotkeyBtn, clearCancelHotkeyBtn;
+let keywordSpoken, keywordReplacement, keywordMatchMode, keywordPreserveCase, addKeywordBtn, keywordsList;
+let keywordImportMode, importKeywordsBtn, keywordImportFile, keywordExportFormat, exportKeywordsBtn, keywordStatus;
 let customPromptEl, savePromptBtn, resetPromptBtn, promptStatus;
+let inputDeviceSelect, refreshDevicesBtn, deviceStatus;
+let formattingModelInput, formattingModelOptions, loadModelsBtn;
+let formattingTemperatureInput, formattingMaxTokensInput, saveModelBtn, modelStatus;
+let formattingModeSelect, autoFormatMinWordsInput;
+let formattingBackendSelect, ollamaUrlRow, ollamaUrlInput, saveOllamaUrlBtn;
+let transcriptionBackendSelect, localModelRow, localModelPathInput, saveModelPathBtn, transcriptionStatus;
+let proxyUrlInput, connectTimeoutInput, requestTimeoutInput, saveNetworkBtn, networkStatus;
+let injectionModeSelect, typingDelayInput, preserveClipboardCheckbox, clipboardRestoreDelayInput, savePasteBtn, pasteStatus;
 
 // DOM elements - Navigation
 let navRecord, navSettings, pageRecord, pageSettings;
@@ -31,7 +34,7 @@ let isRecording = false;
 let formattedResult = '';
 let currentHotkey = 'Control+Space';
 let pendingHotkey = '';
-let registeredShortcut = null;
+let pendingCancelHotkey = '';
 
 // Navigation
 function showPage(page) {
@@ -59,6 +62,27 @@ function showError(message) {
   }, 5000);
 }
 
+// What the user can do about each kind of API or hotkey error
+const ERROR_HINTS = {
+  auth: 'Check your API key in Settings.',
+  quota_exceeded: 'Add credits or raise the usage limit on your OpenAI account.',
+  rate_limited: 'Too many requests right now, wait a moment and try again.',
+  server: 'The service is having problems, try again later.',
+  network: 'Check your internet connection and the endpoint URL.',
+  unavailable: 'Pick a different combination.',
+  audio: 'Check your microphone and record again.',
+  recording_unavailable: 'The recording is no longer cached, record it again.',
+  local_model: 'Check the local Whisper model in Settings, or switch to an API backend.',
+  busy: 'Wait for the current transcription to finish, or cancel it.',
+};
+
+// API and hotkey commands reject with { kind, message }; everything else with a plain string
+function errorMessage(err) {
+  if (!err || typeof err !== 'object') return String(err);
+  const hint = ERROR_HINTS[err.kind];
+  return hint ? `${hint} (${err.message})` : err.message;
+}
+
 function setLoading(loading) {
   loadingEl.classList.toggle('hidden', !loading);
 }
@@ -80,61 +104,70 @@ async function saveApiKey() {
   }
 }
 
-// Recording
+// Recording. The backend runs the whole flow (also for the global hotkey) and
+// reports progress through events, so these just kick it off.
 async function startRecording() {
   if (isRecording) return;
-  try {
-    await invoke('start_recording');
-    await invoke('show_overlay');
-    isRecording = true;
-    recordBtn.classList.add('recording');
-    recordText.textContent = 'Recording...';
-    recordingIndicator.classList.remove('hidden');
-  } catch (err) {
-    showError(`Failed to start recording: ${err}`);
-  }
+  await invoke('start_dictation');
 }
 
 async function stopRecording() {
   if (!isRecording) return;
-  try {
-    await invoke('stop_recording');
-    await invoke('hide_overlay');
-    isRecording = false;
-    recordBtn.classList.remove('recording');
-    recordText.textContent = 'Hold to Record';
-    recordingIndicator.classList.add('hidden');
-    await transcribe();
-  } catch (err) {
-    showError(`Failed to stop recording: ${err}`);
-    await invoke('hide_overlay');
-  }
+  await invoke('stop_dictation');
+}
+
+async function cancelDictation() {
+  await invoke('cancel_dictation');
+}
+
+function setRecording(recording) {
+  isRecording = recording;
+  recordBtn.classList.toggle('recording', recording);
+  recordText.textContent = recording ? 'Recording...' : 'Hold to Record';
+  recordingIndicator.classList.toggle('hidden', !recording);
+}
+
+function resetResults() {
+  rawTextEl.innerHTML = '<p class="placeholder">Raw speech-to-text will appear here...</p>';
+  formattedTextEl.innerHTML = '<p class="placeholder">AI-formatted text will appear here...</p>';
 }
 
-async function transcribe() {
+function showTranscriptionStarted() {
   setLoading(true);
   rawTextEl.innerHTML = '<p class="placeholder">Transcribing...</p>';
   formattedTextEl.innerHTML = '<p class="placeholder">Formatting...</p>';
+}
 
-  try {
-    const result = await invoke('transcribe_audio');
-    rawTextEl.innerHTML = `<p>${escapeHtml(result.raw_text)}</p>`;
-    formattedTextEl.innerHTML = formatTextHtml(result.formatted_text);
-    formattedResult = result.formatted_text;
-    copyBtn.disabled = false;
-    pasteBtn.disabled = false;
+// Auto-paste starts after this arrives and reports back through injection-result
+function showTranscriptionResult(result) {
+  setLoading(false);
+  if (result.outcome === 'cancelled') {
+    rawTextEl.innerHTML = '<p class="placeholder">Transcription cancelled</p>';
+    formattedTextEl.innerHTML = '<p class="placeholder">Nothing to format</p>';
+    return;
+  }
+  if (result.outcome === 'no_speech') {
+    rawTextEl.innerHTML = '<p class="placeholder">No speech detected</p>';
+    formattedTextEl.innerHTML = '<p class="placeholder">Nothing to format</p>';
+    return;
+  }
 
-    // Auto-paste if enabled
-    if (autoPasteCheckbox.checked) {
-      await injectText(result.formatted_text);
-    }
-  } catch (err) {
-    showError(`Transcription failed: ${err}`);
-    rawTextEl.innerHTML = '<p class="placeholder">Raw speech-to-text will appear here...</p>';
-    formattedTextEl.innerHTML = '<p class="placeholder">AI-formatted text will appear here...</p>';
-  } finally {
-    setLoading(false);
+  rawTextEl.innerHTML = `<p>${escapeHtml(result.raw_text)}</p>`;
+  formattedTextEl.innerHTML = formatTextHtml(result.formatted_text);
+  formattedResult = result.formatted_text;
+  if (result.guardrail_triggered) {
+    showError('The formatter replied instead of formatting, so the raw transcription was used');
+  } else if (result.formatting === 'formatter_failed') {
+    showError(`Formatting failed, so the raw transcription was used: ${errorMessage(result.formatting_error)}`);
   }
+  copyBtn.disabled = false;
+  pasteBtn.disabled = false;
+}
+
+function showTranscriptionError(err) {
+  setLoading(false);
+  showError(`Transcription failed: ${errorMessage(err)}`);
+  resetResults();
 }
 
 function escapeHtml(text) {
@@ -143,6 +176,11 @@ function escapeHtml(text) {
   return div.innerHTML;
 }
 
+// For values inside double-quoted attributes, which innerHTML doesn't escape quotes for
+function escapeAttr(text) {
+  return escapeHtml(text).replace(/"/g, '&quot;');
+}
+
 function formatTextHtml(text) {
   let html = escapeHtml(text);
   html = html.replace(/^[-*]\s+(.+)$/gm, '<li>$1</li>');
@@ -167,48 +205,22 @@ async function injectText(text) {
   try {
     await invoke('inject_text', { text: text || formattedResult });
   } catch (err) {
-    showError(`Failed to paste: ${err}`);
+    showInjectionError(err);
   }
 }
 
-// Hotkey management
-async function registerHotkey(hotkey) {
-  if (!globalShortcut) {
-    console.log('Global shortcut not available');
-    return;
-  }
-
-  try {
-    // Unregister previous shortcut
-    if (registeredShortcut) {
-      try {
-        await globalShortcut.unregister(registeredShortcut);
-      } catch (e) {
-        console.log('Failed to unregister previous shortcut:', e);
-      }
-    }
-
-    // Register new shortcut
-    await globalShortcut.register(hotkey, async (event) => {
-      if (event.state === 'Pressed') {
-        await startRecording();
-      } else if (event.state === 'Released') {
-        await stopRecording();
-      }
-    });
-
-    registeredShortcut = hotkey;
-    currentHotkey = hotkey;
-    hotkeyDisplay.textContent = hotkey;
-    console.log(`Registered hotkey: ${hotkey}`);
-  } catch (err) {
-    console.error('Failed to register hotkey:', err);
-    showError(`Failed to register hotkey: ${err}`);
+// Injection rejects with { message, copied }; when copied, the text is waiting on the clipboard
+function showInjectionError(err) {
+  if (err && err.copied) {
+    showError(`Couldn't paste automatically, the text is on your clipboard. Paste it manually. (${err.message})`);
+  } else {
+    showError(`Failed to paste: ${errorMessage(err)}`);
   }
 }
 
-function handleHotkeyCapture(e) {
-  e.preventDefault();
+// Hotkey management
+// Turn a keydown event into a shortcut string like "Control+Shift+K"
+function shortcutFromEvent(e) {
   const parts = [];
   if (e.ctrlKey) parts.push('Control');
   if (e.altKey) parts.push('Alt');
@@ -225,12 +237,27 @@ function handleHotkeyCapture(e) {
     parts.push(normalizedKey);
   }
 
-  if (parts.length > 0) {
-    pendingHotkey = parts.join('+');
+  return parts.join('+');
+}
+
+function handleHotkeyCapture(e) {
+  e.preventDefault();
+  const shortcut = shortcutFromEvent(e);
+  if (shortcut) {
+    pendingHotkey = shortcut;
     hotkeyInput.value = pendingHotkey;
   }
 }
 
+function handleCancelHotkeyCapture(e) {
+  e.preventDefault();
+  const shortcut = shortcutFromEvent(e);
+  if (shortcut) {
+    pendingCancelHotkey = shortcut;
+    cancelHotkeyInput.value = pendingCancelHotkey;
+  }
+}
+
 async function saveHotkey() {
   if (!pendingHotkey) {
     showStatus(hotkeyStatus, 'Please press a key combination first', 'error');
@@ -238,10 +265,124 @@ async function saveHotkey() {
   }
   try {
     await invoke('set_hotkey', { hotkey: pendingHotkey });
-    await registerHotkey(pendingHotkey);
+    currentHotkey = pendingHotkey;
+    hotkeyDisplay.textContent = pendingHotkey;
     showStatus(hotkeyStatus, 'Hotkey saved!', 'success');
   } catch (err) {
-    showStatus(hotkeyStatus, `Error: ${err}`, 'error');
+    // The previous hotkey is still active
+    hotkeyInput.value = currentHotkey;
+    pendingHotkey = '';
+    showStatus(hotkeyStatus, `Error: ${errorMessage(err)}`, 'error');
+  }
+}
+
+async function saveCancelHotkey() {
+  if (!pendingCancelHotkey) {
+    showStatus(hotkeyStatus, 'Please press a key combination first', 'error');
+    return;
+  }
+  try {
+    await invoke('set_cancel_hotkey', { hotkey: pendingCancelHotkey });
+    showStatus(hotkeyStatus, 'Cancel hotkey saved!', 'success');
+  } catch (err) {
+    cancelHotkeyInput.value = (await invoke('get_cancel_hotkey')) || '';
+    pendingCancelHotkey = '';
+    showStatus(hotkeyStatus, `Error: ${errorMessage(err)}`, 'error');
+  }
+}
+
+async function saveHotkeyMode() {
+  try {
+    await invoke('set_hotkey_mode', { mode: hotkeyModeSelect.value });
+    showStatus(hotkeyStatus, 'Hotkey mode saved!', 'success');
+  } catch (err) {
+    showStatus(hotkeyStatus, `Error: ${errorMessage(err)}`, 'error');
+  }
+}
+
+async function clearCancelHotkey() {
+  try {
+    await invoke('set_cancel_hotkey', { hotkey: null });
+    pendingCancelHotkey = '';
+    cancelHotkeyInput.value = '';
+    showStatus(hotkeyStatus, 'Cancel hotkey removed', 'success');
+  } catch (err) {
+    showStatus(hotkeyStatus, `Error: ${errorMessage(err)}`, 'error');
+  }
+}
+
+// Transcription backend
+async function loadTranscriptionSettings() {
+  try {
+    const settings = await invoke('get_settings');
+    transcriptionBackendSelect.value = settings.transcription_backend;
+    localModelPathInput.value = settings.local_model_path || '';
+    localModelRow.classList.toggle('hidden', settings.transcription_backend !== 'local');
+  } catch (err) {
+    console.error('Failed to load transcription settings:', err);
+  }
+}
+
+async function saveTranscriptionBackend() {
+  const backend = transcriptionBackendSelect.value;
+  localModelRow.classList.toggle('hidden', backend !== 'local');
+  try {
+    await invoke('set_transcription_backend', { backend });
+    showStatus(transcriptionStatus, 'Transcription backend saved!', 'success');
+  } catch (err) {
+    showStatus(transcriptionStatus, `Error: ${err}`, 'error');
+  }
+}
+
+async function saveLocalModelPath() {
+  const path = localModelPathInput.value.trim() || null;
+  try {
+    await invoke('set_local_model_path', { path });
+    showStatus(transcriptionStatus, 'Model path saved!', 'success');
+  } catch (err) {
+    showStatus(transcriptionStatus, `Error: ${err}`, 'error');
+  }
+}
+
+// Input device management
+async function loadInputDevices() {
+  try {
+    const [devices, selected] = await Promise.all([
+      invoke('list_input_devices'),
+      invoke('get_input_device'),
+    ]);
+    renderInputDevices(devices, selected);
+  } catch (err) {
+    console.error('Failed to load input devices:', err);
+    showStatus(deviceStatus, `Error: ${err}`, 'error');
+  }
+}
+
+function renderInputDevices(devices, selected) {
+  const defaultDevice = devices.find(d => d.is_default);
+  const defaultLabel = defaultDevice ? `System default (${defaultDevice.name})` : 'System default';
+
+  const options = [`<option value="">${escapeHtml(defaultLabel)}</option>`];
+  for (const device of devices) {
+    options.push(`<option value="${escapeAttr(device.name)}">${escapeHtml(device.name)}</option>`);
+  }
+
+  // Keep a saved device visible even if it's currently unplugged
+  if (selected && !devices.some(d => d.name === selected)) {
+    options.push(`<option value="${escapeAttr(selected)}">${escapeHtml(selected)} (not connected)</option>`);
+  }
+
+  inputDeviceSelect.innerHTML = options.join('');
+  inputDeviceSelect.value = selected || '';
+}
+
+async function saveInputDevice() {
+  const device = inputDeviceSelect.value || null;
+  try {
+    await invoke('set_input_device', { device });
+    showStatus(deviceStatus, 'Microphone saved!', 'success');
+  } catch (err) {
+    showStatus(deviceStatus, `Error: ${err}`, 'error');
   }
 }
 
@@ -256,27 +397,45 @@ async function loadKeywords() {
 }
 
 function renderKeywords(keywords) {
-  const entries = Object.entries(keywords);
-  if (entries.length === 0) {
+  if (keywords.length === 0) {
     keywordsList.innerHTML = '<p class="placeholder">No keywords added yet</p>';
     return;
   }
 
-  keywordsList.innerHTML = entries.map(([spoken, replacement]) => `
-    <div class="keyword-item">
-      <span class="keyword-spoken">${escapeHtml(spoken)}</span>
+  const tags = { substring: 'anywhere', regex: 'regex' };
+  keywordsList.innerHTML = keywords.map((rule) => `
+    <div class="keyword-item${rule.enabled ? '' : ' disabled'}">
+      <input type="checkbox" class="keyword-enabled" data-spoken="${escapeAttr(rule.spoken)}" data-mode="${rule.match_mode}" ${rule.enabled ? 'checked' : ''} title="Enabled" />
+      <span class="keyword-spoken">${escapeHtml(rule.spoken)}</span>
       <span class="arrow">→</span>
-      <span class="keyword-replacement">${escapeHtml(replacement)}</span>
-      <button class="remove-keyword" data-spoken="${escapeHtml(spoken)}">×</button>
+      <span class="keyword-replacement">${escapeHtml(rule.replacement)}</span>
+      ${tags[rule.match_mode] ? `<span class="keyword-mode">${tags[rule.match_mode]}</span>` : ''}
+      ${rule.preserve_case ? '<span class="keyword-mode">keeps case</span>' : ''}
+      <button class="remove-keyword" data-spoken="${escapeAttr(rule.spoken)}" data-mode="${rule.match_mode}">×</button>
     </div>
   `).join('');
 
+  keywordsList.querySelectorAll('.keyword-enabled').forEach(checkbox => {
+    checkbox.addEventListener('change', async () => {
+      try {
+        await invoke('set_keyword_enabled', {
+          spoken: checkbox.dataset.spoken,
+          matchMode: checkbox.dataset.mode,
+          enabled: checkbox.checked,
+        });
+        await loadKeywords();
+      } catch (err) {
+        showError(`Failed to update keyword: ${err}`);
+      }
+    });
+  });
+
   // Add remove handlers
   keywordsList.querySelectorAll('.remove-keyword').forEach(btn => {
     btn.addEventListener('click', async () => {
-      const spoken = btn.dataset.spoken;
+      const { spoken, mode } = btn.dataset;
       try {
-        await invoke('remove_keyword', { spoken });
+        await invoke('remove_keyword', { spoken, matchMode: mode });
         await loadKeywords();
       } catch (err) {
         showError(`Failed to remove keyword: ${err}`);
@@ -295,15 +454,243 @@ async function addKeyword() {
   }
 
   try {
-    await invoke('add_keyword', { spoken, replacement });
+    await invoke('add_keyword', {
+      spoken,
+      replacement,
+      matchMode: keywordMatchMode.value,
+      preserveCase: keywordPreserveCase.checked,
+    });
     keywordSpoken.value = '';
     keywordReplacement.value = '';
+    keywordMatchMode.value = 'whole_word';
+    keywordPreserveCase.checked = false;
     await loadKeywords();
   } catch (err) {
     showError(`Failed to add keyword: ${err}`);
   }
 }
 
+// Keyword import/export
+async function importKeywords() {
+  const file = keywordImportFile.files[0];
+  keywordImportFile.value = '';
+  if (!file) return;
+
+  const extension = file.name.split('.').pop().toLowerCase();
+  const args = {
+    content: await file.text(),
+    format: ['csv', 'json'].includes(extension) ? extension : null,
+    mode: keywordImportMode.value,
+  };
+  try {
+    let report = await invoke('import_keywords', args);
+    if (!report.applied) {
+      // A replace that would skip rows or empty the list waits for confirmation
+      showImportReport(report);
+      const problem = report.invalid.length
+        ? `${report.invalid.length} row(s) couldn't be read`
+        : 'the file has no keyword rules';
+      if (!confirm(`Replace all keywords? ${problem}, and ${report.removed} existing rule(s) will be removed.`)) {
+        return;
+      }
+      report = await invoke('import_keywords', { ...args, force: true });
+    }
+    await loadKeywords();
+    showImportReport(report);
+  } catch (err) {
+    showStatus(keywordStatus, `Import failed: ${err}`, 'error');
+  }
+}
+
+// Stays on screen (unlike showStatus) so skipped rows can be read
+function showImportReport(report) {
+  const counts = `${report.added} added, ${report.updated} updated, ${report.unchanged} unchanged, ${report.removed} removed`;
+  const lines = [report.applied ? `Imported: ${counts}` : `Not imported yet: ${counts}`];
+  report.conflicts.forEach(c => {
+    lines.push(`Changed "${c.spoken}": ${c.existing} → ${c.imported}`);
+  });
+  report.duplicates.forEach(d => {
+    lines.push(`Row ${d.row}: "${d.spoken}" is a duplicate, the earlier row was used`);
+  });
+  report.invalid.forEach(i => {
+    lines.push(`Row ${i.row}: skipped, ${i.message}`);
+  });
+  keywordStatus.textContent = lines.join('\n');
+  keywordStatus.className = `status ${report.invalid.length ? 'error' : 'success'}`;
+}
+
+async function exportKeywords() {
+  try {
+    const path = await invoke('export_keywords', { format: keywordExportFormat.value });
+    showStatus(keywordStatus, `Exported to ${path}`, 'success');
+  } catch (err) {
+    showStatus(keywordStatus, `Export failed: ${err}`, 'error');
+  }
+}
+
+// Formatting model management
+async function loadFormattingModel() {
+  try {
+    const [model, temperature, maxTokens] = await Promise.all([
+      invoke('get_formatting_model'),
+      invoke('get_formatting_temperature'),
+      invoke('get_formatting_max_tokens'),
+    ]);
+    formattingModelInput.value = model;
+    formattingTemperatureInput.value = temperature;
+    formattingMaxTokensInput.value = maxTokens ?? '';
+
+    const settings = await invoke('get_settings');
+    formattingModeSelect.value = settings.formatting_mode;
+    autoFormatMinWordsInput.value = settings.auto_format_min_words;
+    formattingBackendSelect.value = settings.formatting_backend;
+    ollamaUrlInput.value = settings.ollama_endpoint.base_url;
+    ollamaUrlRow.classList.toggle('hidden', settings.formatting_backend !== 'ollama');
+  } catch (err) {
+    console.error('Failed to load formatting model:', err);
+  }
+}
+
+// Text insertion
+async function loadPasteSettings() {
+  try {
+    const settings = await invoke('get_settings');
+    injectionModeSelect.value = settings.injection_mode;
+    typingDelayInput.value = settings.typing_delay_ms;
+    preserveClipboardCheckbox.checked = settings.preserve_clipboard;
+    clipboardRestoreDelayInput.value = settings.clipboard_restore_delay_ms;
+  } catch (err) {
+    console.error('Failed to load paste settings:', err);
+  }
+}
+
+async function savePasteSettings() {
+  const typingDelayMs = parseInt(typingDelayInput.value, 10);
+  const clipboardRestoreDelayMs = parseInt(clipboardRestoreDelayInput.value, 10);
+  if (!(typingDelayMs >= 0) || !(clipboardRestoreDelayMs >= 0)) {
+    showStatus(pasteStatus, 'Delays must be 0 or more milliseconds', 'error');
+    return;
+  }
+  try {
+    await invoke('set_paste_settings', {
+      injectionMode: injectionModeSelect.value,
+      typingDelayMs,
+      preserveClipboard: preserveClipboardCheckbox.checked,
+      clipboardRestoreDelayMs,
+    });
+    showStatus(pasteStatus, 'Paste settings saved!', 'success');
+  } catch (err) {
+    showStatus(pasteStatus, `Error: ${err}`, 'error');
+  }
+}
+
+// Network
+async function loadNetworkSettings() {
+  try {
+    const settings = await invoke('get_settings');
+    proxyUrlInput.value = settings.proxy_url || '';
+    connectTimeoutInput.value = settings.connect_timeout_secs;
+    requestTimeoutInput.value = settings.request_timeout_secs;
+  } catch (err) {
+    console.error('Failed to load network settings:', err);
+  }
+}
+
+async function saveNetworkSettings() {
+  const connectTimeoutSecs = parseInt(connectTimeoutInput.value, 10);
+  const requestTimeoutSecs = parseInt(requestTimeoutInput.value, 10);
+  if (!(connectTimeoutSecs >= 1) || !(requestTimeoutSecs >= 1)) {
+    showStatus(networkStatus, 'Timeouts must be at least 1 second', 'error');
+    return;
+  }
+  try {
+    await invoke('set_network_settings', {
+      connectTimeoutSecs,
+      requestTimeoutSecs,
+      proxyUrl: proxyUrlInput.value.trim() || null,
+    });
+    showStatus(networkStatus, 'Network settings saved!', 'success');
+  } catch (err) {
+    showStatus(networkStatus, `Error: ${err}`, 'error');
+  }
+}
+
+async function saveFormattingBackend() {
+  const backend = formattingBackendSelect.value;
+  ollamaUrlRow.classList.toggle('hidden', backend !== 'ollama');
+  try {
+    await invoke('set_formatting_backend', { backend });
+    // Each backend keeps its own model
+    formattingModelInput.value = await invoke('get_formatting_model');
+    formattingModelOptions.innerHTML = '';
+    showStatus(modelStatus, 'Formatting backend saved!', 'success');
+  } catch (err) {
+    showStatus(modelStatus, `Error: ${err}`, 'error');
+  }
+}
+
+async function saveOllamaUrl() {
+  const baseUrl = ollamaUrlInput.value.trim();
+  if (!baseUrl) {
+    showStatus(modelStatus, 'Please enter the Ollama URL', 'error');
+    return;
+  }
+  try {
+    const settings = await invoke('get_settings');
+    await invoke('set_ollama_endpoint', {
+      endpoint: { ...settings.ollama_endpoint, base_url: baseUrl },
+    });
+    showStatus(modelStatus, 'Ollama URL saved!', 'success');
+  } catch (err) {
+    showStatus(modelStatus, `Error: ${err}`, 'error');
+  }
+}
+
+async function loadAvailableModels() {
+  loadModelsBtn.disabled = true;
+  try {
+    const models = await invoke('list_models', { endpoint: 'formatting' });
+    formattingModelOptions.innerHTML = models
+      .map(m => `<option value="${escapeAttr(m)}"></option>`)
+      .join('');
+    showStatus(modelStatus, `Found ${models.length} models`, 'success');
+  } catch (err) {
+    showStatus(modelStatus, `Error: ${errorMessage(err)}`, 'error');
+  } finally {
+    loadModelsBtn.disabled = false;
+  }
+}
+
+async function saveFormattingModel() {
+  const model = formattingModelInput.value.trim();
+  const temperature = parseFloat(formattingTemperatureInput.value);
+  const maxTokensValue = formattingMaxTokensInput.value.trim();
+  const maxTokens = maxTokensValue ? parseInt(maxTokensValue, 10) : null;
+
+  if (!model) {
+    showStatus(modelStatus, 'Please enter a model name', 'error');
+    return;
+  }
+  if (Number.isNaN(temperature)) {
+    showStatus(modelStatus, 'Please enter a temperature', 'error');
+    return;
+  }
+
+  try {
+    await invoke('set_formatting_model', { model });
+    await invoke('set_formatting_temperature', { temperature });
+    await invoke('set_formatting_max_tokens', { maxTokens });
+    await invoke('set_formatting_mode', { mode: formattingModeSelect.value });
+    const minWords = parseInt(autoFormatMinWordsInput.value, 10);
+    if (!Number.isNaN(minWords)) {
+      await invoke('set_auto_format_min_words', { words: minWords });
+    }
+    showStatus(modelStatus, 'Model settings saved!', 'success');
+  } catch (err) {
+    showStatus(modelStatus, `Error: ${err}`, 'error');
+  }
+}
+
 // Prompt management
 async function loadPrompt() {
   try {
@@ -345,12 +732,24 @@ async function loadSettings() {
     currentHotkey = hotkey;
     hotkeyInput.value = hotkey;
     hotkeyDisplay.textContent = hotkey;
-    await registerHotkey(hotkey);
+
+    const cancelHotkey = await invoke('get_cancel_hotkey');
+    cancelHotkeyInput.value = cancelHotkey || '';
+
+    const settings = await invoke('get_settings');
+    hotkeyModeSelect.value = settings.hotkey_mode;
+    // Auto-paste is done by the backend, so show what it will actually do
+    autoPasteCheckbox.checked = settings.auto_paste;
   } catch (err) {
     console.error('Failed to load hotkey:', err);
   }
 
+  await loadTranscriptionSettings();
+  await loadInputDevices();
   await loadKeywords();
+  await loadFormattingModel();
+  await loadPasteSettings();
+  await loadNetworkSettings();
   await loadPrompt();
 }
 
@@ -411,6 +810,7 @@ window.addEventListener("DOMContentLoaded", async () => {
   pasteBtn = document.getElementById('paste-btn');
   errorMsg = document.getElementById('error-msg');
   loadingEl = document.getElementById('loading');
+  cancelBtn = document.getElementById('cancel-btn');
   autoPasteCheckbox = document.getElementById('auto-paste');
   hotkeyDisplay = document.getElementById('hotkey-display');
 
@@ -418,14 +818,58 @@ window.addEventListener("DOMContentLoaded", async () => {
   hotkeyInput = document.getElementById('hotkey-input');
   saveHotkeyBtn = document.getElementById('save-hotkey-btn');
   hotkeyStatus = document.getElementById('hotkey-status');
+  hotkeyModeSelect = document.getElementById('hotkey-mode');
+  cancelHotkeyInput = document.getElementById('cancel-hotkey-input');
+  saveCancelHotkeyBtn = document.getElementById('save-cancel-hotkey-btn');
+  clearCancelHotkeyBtn = document.getElementById('clear-cancel-hotkey-btn');
   keywordSpoken = document.getElementById('keyword-spoken');
   keywordReplacement = document.getElementById('keyword-replacement');
+  keywordMatchMode = document.getElementById('keyword-match-mode');
+  keywordPreserveCase = document.getElementById('keyword-preserve-case');
   addKeywordBtn = document.getElementById('add-keyword-btn');
   keywordsList = document.getElementById('keywords-list');
+  keywordImportMode = document.getElementById('keyword-import-mode');
+  importKeywordsBtn = document.getElementById('import-keywords-btn');
+  keywordImportFile = document.getElementById('keyword-import-file');
+  keywordExportFormat = document.getElementById('keyword-export-format');
+  exportKeywordsBtn = document.getElementById('export-keywords-btn');
+  keywordStatus = document.getElementById('keyword-status');
   customPromptEl = document.getElementById('custom-prompt');
   savePromptBtn = document.getElementById('save-prompt-btn');
   resetPromptBtn = document.getElementById('reset-prompt-btn');
   promptStatus = document.getElementById('prompt-status');
+  inputDeviceSelect = document.getElementById('input-device');
+  refreshDevicesBtn = document.getElementById('refresh-devices-btn');
+  deviceStatus = document.getElementById('device-status');
+  formattingModelInput = document.getElementById('formatting-model');
+  formattingModelOptions = document.getElementById('formatting-model-options');
+  loadModelsBtn = document.getElementById('load-models-btn');
+  formattingTemperatureInput = document.getElementById('formatting-temperature');
+  formattingMaxTokensInput = document.getElementById('formatting-max-tokens');
+  saveModelBtn = document.getElementById('save-model-btn');
+  modelStatus = document.getElementById('model-status');
+  formattingModeSelect = document.getElementById('formatting-mode');
+  autoFormatMinWordsInput = document.getElementById('auto-format-min-words');
+  formattingBackendSelect = document.getElementById('formatting-backend');
+  ollamaUrlRow = document.getElementById('ollama-url-row');
+  ollamaUrlInput = document.getElementById('ollama-url');
+  saveOllamaUrlBtn = document.getElementById('save-ollama-url-btn');
+  transcriptionBackendSelect = document.getElementById('transcription-backend');
+  localModelRow = document.getElementById('local-model-row');
+  localModelPathInput = document.getElementById('local-model-path');
+  saveModelPathBtn = document.getElementById('save-model-path-btn');
+  transcriptionStatus = document.getElementById('transcription-status');
+  proxyUrlInput = document.getElementById('proxy-url');
+  connectTimeoutInput = document.getElementById('connect-timeout');
+  requestTimeoutInput = document.getElementById('request-timeout');
+  saveNetworkBtn = document.getElementById('save-network-btn');
+  networkStatus = document.getElementById('network-status');
+  injectionModeSelect = document.getElementById('injection-mode');
+  typingDelayInput = document.getElementById('typing-delay');
+  preserveClipboardCheckbox = document.getElementById('preserve-clipboard');
+  clipboardRestoreDelayInput = document.getElementById('clipboard-restore-delay');
+  savePasteBtn = document.getElementById('save-paste-btn');
+  pasteStatus = document.getElementById('paste-status');
 
   // Get DOM elements - Navigation
   navRecord = document.getElementById('nav-record');
@@ -476,13 +920,65 @@ window.addEventListener("DOMContentLoaded", async () => {
     pendingHotkey = '';
   });
   saveHotkeyBtn.addEventListener('click', saveHotkey);
+  hotkeyModeSelect.addEventListener('change', saveHotkeyMode);
+  cancelHotkeyInput.addEventListener('keydown', handleCancelHotkeyCapture);
+  cancelHotkeyInput.addEventListener('focus', () => {
+    cancelHotkeyInput.value = 'Press keys...';
+    pendingCancelHotkey = '';
+  });
+  saveCancelHotkeyBtn.addEventListener('click', saveCancelHotkey);
+  clearCancelHotkeyBtn.addEventListener('click', clearCancelHotkey);
+  cancelBtn.addEventListener('click', cancelDictation);
+
+  // Dictation progress, from the record button or the global hotkey
+  await listen('recording-started', () => setRecording(true));
+  await listen('recording-stopped', () => setRecording(false));
+  await listen('recording-error', (event) => {
+    setRecording(false);
+    showError(`Failed to start recording: ${event.payload}`);
+  });
+  await listen('transcription-started', showTranscriptionStarted);
+  await listen('transcription-result', (event) => showTranscriptionResult(event.payload));
+  await listen('transcription-error', (event) => showTranscriptionError(event.payload));
+  await listen('injection-result', (event) => {
+    if (!event.payload.success) showInjectionError(event.payload.error);
+  });
+
+  // Transcription events
+  transcriptionBackendSelect.addEventListener('change', saveTranscriptionBackend);
+  saveModelPathBtn.addEventListener('click', saveLocalModelPath);
+
+  // Input device events
+  inputDeviceSelect.addEventListener('change', saveInputDevice);
+  refreshDevicesBtn.addEventListener('click', loadInputDevices);
+
+  // Notify when the saved microphone is missing
+  await listen('input-device-fallback', (event) => {
+    const { requested, fallback } = event.payload;
+    showError(`Microphone "${requested}" not found, using ${fallback || 'the default device'}`);
+  });
 
   // Keyword events
   addKeywordBtn.addEventListener('click', addKeyword);
+  importKeywordsBtn.addEventListener('click', () => keywordImportFile.click());
+  keywordImportFile.addEventListener('change', importKeywords);
+  exportKeywordsBtn.addEventListener('click', exportKeywords);
   keywordReplacement.addEventListener('keypress', (e) => {
     if (e.key === 'Enter') addKeyword();
   });
 
+  // Formatting model events
+  formattingBackendSelect.addEventListener('change', saveFormattingBackend);
+  saveOllamaUrlBtn.addEventListener('click', saveOllamaUrl);
+  loadModelsBtn.addEventListener('click', loadAvailableModels);
+  saveModelBtn.addEventListener('click', saveFormattingModel);
+
+  // Paste events
+  savePasteBtn.addEventListener('click', savePasteSettings);
+
+  // Network events
+  saveNetworkBtn.addEventListener('click', saveNetworkSettings);
+
   // Prompt events
   savePromptBtn.addEventListener('click', savePrompt);
   resetPromptBtn.addEventListener('click', resetPrompt);